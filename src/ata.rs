@@ -0,0 +1,158 @@
+// Keeps the high-score table in the first sector of the disk attached as the
+// primary ATA slave (the boot image is the master), using polled PIO. With no
+// disk there, loads come back empty and saves are dropped.
+
+use x86_64::instructions::port::Port;
+
+use crate::game_core::high_scores::{ScoreStorage, RECORD_SIZE};
+
+const SECTOR_SIZE: usize = 512;
+const SCORE_SECTOR: u32 = 0;
+
+const DATA: u16 = 0x1F0;
+const SECTOR_COUNT: u16 = 0x1F2;
+const LBA_LOW: u16 = 0x1F3;
+const LBA_MID: u16 = 0x1F4;
+const LBA_HIGH: u16 = 0x1F5;
+const DRIVE: u16 = 0x1F6;
+const STATUS: u16 = 0x1F7;
+const COMMAND: u16 = 0x1F7;
+
+const SELECT_SLAVE_LBA: u8 = 0xF0;
+const READ_SECTORS: u8 = 0x20;
+const WRITE_SECTORS: u8 = 0x30;
+const CACHE_FLUSH: u8 = 0xE7;
+const IDENTIFY: u8 = 0xEC;
+
+const ERR: u8 = 0x01;
+const DRQ: u8 = 0x08;
+const DF: u8 = 0x20;
+const BSY: u8 = 0x80;
+const NO_DRIVE: u8 = 0xFF; // nothing pulls the bus down
+
+// Status polls before giving up on the drive
+const MAX_POLLS: usize = 100_000;
+
+pub struct AtaStorage {
+    present: Option<bool> // found out on first use
+}
+
+impl AtaStorage {
+    pub const fn new() -> Self {
+        AtaStorage {present: None}
+    }
+
+    fn present(&mut self) -> bool {
+        *self.present.get_or_insert_with(identify)
+    }
+}
+
+impl ScoreStorage for AtaStorage {
+    fn load(&mut self) -> Option<[u8; RECORD_SIZE]> {
+        if !self.present() {
+            return None
+        }
+        let mut sector = [0; SECTOR_SIZE];
+        read_sector(SCORE_SECTOR, &mut sector)?;
+        let mut record = [0; RECORD_SIZE];
+        record.copy_from_slice(&sector[..RECORD_SIZE]);
+        Some(record)
+    }
+
+    fn save(&mut self, record: &[u8; RECORD_SIZE]) {
+        if self.present() {
+            let mut sector = [0; SECTOR_SIZE];
+            sector[..RECORD_SIZE].copy_from_slice(record);
+            write_sector(SCORE_SECTOR, &sector);
+        }
+    }
+}
+
+fn status() -> u8 {
+    unsafe { Port::<u8>::new(STATUS).read() }
+}
+
+fn command(command: u8) {
+    unsafe { Port::<u8>::new(COMMAND).write(command) }
+}
+
+// Waits for the drive to finish, then for it to want data; None on errors
+// and timeouts
+fn wait_for_data() -> Option<()> {
+    for _ in 0..MAX_POLLS {
+        let status = status();
+        if status & BSY == 0 {
+            if status & (ERR | DF) != 0 {
+                return None
+            }
+            if status & DRQ != 0 {
+                return Some(())
+            }
+        }
+    }
+    None
+}
+
+fn wait_until_idle() -> Option<()> {
+    for _ in 0..MAX_POLLS {
+        if status() & BSY == 0 {
+            return Some(())
+        }
+    }
+    None
+}
+
+fn select(lba: u32, sectors: u8) {
+    unsafe {
+        Port::<u8>::new(DRIVE).write(SELECT_SLAVE_LBA | ((lba >> 24) & 0x0F) as u8);
+        Port::<u8>::new(SECTOR_COUNT).write(sectors);
+        Port::<u8>::new(LBA_LOW).write(lba as u8);
+        Port::<u8>::new(LBA_MID).write((lba >> 8) as u8);
+        Port::<u8>::new(LBA_HIGH).write((lba >> 16) as u8);
+    }
+}
+
+// True if an ATA disk answers as the primary slave
+fn identify() -> bool {
+    select(0, 0);
+    command(IDENTIFY);
+    let status = status();
+    if status == 0 || status == NO_DRIVE || wait_until_idle().is_none() {
+        return false
+    }
+    // ATAPI and SATA devices announce themselves here instead
+    let signature = unsafe { (Port::<u8>::new(LBA_MID).read(), Port::<u8>::new(LBA_HIGH).read()) };
+    if signature != (0, 0) || wait_for_data().is_none() {
+        return false
+    }
+    let mut data: Port<u16> = Port::new(DATA);
+    for _ in 0..SECTOR_SIZE / 2 {
+        unsafe { data.read(); }
+    }
+    true
+}
+
+fn read_sector(lba: u32, sector: &mut [u8; SECTOR_SIZE]) -> Option<()> {
+    wait_until_idle()?;
+    select(lba, 1);
+    command(READ_SECTORS);
+    wait_for_data()?;
+    let mut data: Port<u16> = Port::new(DATA);
+    for word in sector.chunks_mut(2) {
+        word.copy_from_slice(&unsafe { data.read() }.to_le_bytes());
+    }
+    Some(())
+}
+
+fn write_sector(lba: u32, sector: &[u8; SECTOR_SIZE]) -> Option<()> {
+    wait_until_idle()?;
+    select(lba, 1);
+    command(WRITE_SECTORS);
+    wait_for_data()?;
+    let mut data: Port<u16> = Port::new(DATA);
+    for word in sector.chunks(2) {
+        unsafe { data.write(u16::from_le_bytes([word[0], word[1]])) }
+    }
+    command(CACHE_FLUSH);
+    wait_until_idle()
+}
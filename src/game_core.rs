@@ -1,34 +1,136 @@
 #![allow(incomplete_features)]
-#![cfg_attr(not(test), no_std)]
 
 
-use pc_keyboard::{DecodedKey, KeyCode};
-use pluggable_interrupt_os::vga_buffer::{BUFFER_WIDTH, BUFFER_HEIGHT};
-use core::borrow::BorrowMut;
-use pluggable_interrupt_os::println;
-
-const WIDTH: usize = BUFFER_WIDTH;
-const HEIGHT: usize = BUFFER_HEIGHT - 2;
+use pc_keyboard::{DecodedKey, KeyEvent};
+use crate::game_core::level::{Level, LevelError, LevelErrorKind, LEVELS};
+use crate::game_core::high_scores::{HighScores, INITIALS_LEN};
+use crate::game_core::input::{Action, KeyBindings, KeyInput, HeldKeys, ACTIONS, PRESETS, normalize};
+use crate::game_core::rng::Rng;
+use crate::game_core::shots::{ShotPool, Shot, ShotKind, PLAYER_SHOTS, MAX_ROWS_PER_UPDATE};
+use crate::game_core::occupancy::{Occupancy, ShotId};
+
+pub mod ai;
+pub mod high_scores;
+pub mod input;
+pub mod console;
+pub mod level;
+mod occupancy;
+pub mod replay;
+pub mod rng;
+pub mod shots;
+#[cfg(test)]
+mod fuzz;
+#[cfg(test)]
+pub(crate) mod sim;
+#[cfg(test)]
+mod tests;
+
+// The game is generic over its board size. The kernel's board fills the
+// 80x25 VGA text buffer apart from the two header rows; the built-in levels
+// are drawn for it and fit any board at least as big.
+pub const BOARD_WIDTH: usize = 80;
+pub const BOARD_HEIGHT: usize = 23;
+pub const MAX_ALIENS: usize = 160;
 const UPDATE_FREQUENCY: usize = 1;
+const WAVE_PAUSE: usize = 30; // updates spent showing the "Wave N" banner
+const MAX_WAVE_DROP: usize = 5; // rows the formation can start below its level position
+pub const STARTING_LIVES: u8 = 3;
+pub const MAX_LIVES: u8 = 9;
+pub const BARRIER_HEALTH: u8 = 4; // hits an undamaged bunker cell takes
+const EXTRA_LIFE_SCORE: u64 = 2000; // an extra life every this many points
+const RESPAWN_TIME: usize = 20; // updates the board stays frozen after the player is hit
+const EXPLOSION_TIME: usize = 10; // the first part of the respawn pause shows the explosion
+const UFO_INTERVAL: usize = 150; // updates between mystery ship fly-bys
+const UFO_POPUP_TIME: usize = 15; // updates the bonus stays on screen
+const UFO_WIDTH: i16 = 3;
+const INVASION_HEIGHT: usize = 7; // the formation lands on reaching this many rows from the bottom
+// Arcade bonus table, indexed by how many shots the player has fired
+const UFO_POINTS: [u64; 15] = [100, 50, 50, 100, 150, 100, 100, 50, 300, 100, 100, 100, 50, 150, 100];
+const MARCH_SLOWEST: usize = 8; // updates between march notes with the full formation
+const MARCH_FASTEST: usize = 1; // updates between march notes with one alien left
+const MARCH_NOTES: u8 = 4;
+const MAX_SOUNDS: usize = 8; // sounds queued between two drains
+const FIRE_AIM_RANGE: usize = 10; // columns either side of the player that draw extra fire
+const FIRE_AIM_BIAS: usize = 4; // extra weight per column closer to the player
+const TURN_PAUSE: usize = 30; // updates spent showing whose turn it is
+const TITLE_TIME: usize = 90; // updates the title screen waits for a key before the demo
+const DEMO_TIME: usize = 600; // updates of demo play before the title screen returns
 
 #[derive(Copy,Clone,Eq,PartialEq,Debug)]
-pub struct SpaceInvadersGame {
+pub struct SpaceInvadersGame<const WIDTH: usize, const HEIGHT: usize> {
+    levels: &'static [&'static str], // level text, one per wave, repeating once they run out
     cells: [[Cell; WIDTH]; HEIGHT],
     status: Status,
     player: Player,
     aliens: Aliens,
-    shots: [Shot; 4],
+    player_shots: ShotPool,
+    alien_shots: ShotPool,
+    occupancy: Occupancy<WIDTH, HEIGHT>,
     score: u64,
     last_dir: Option<Dir>,
     countdown: usize,
     fired_shot: bool,
     alien_move_frequency: usize,
-    alien_move_countdown: usize // delays alien movement
+    alien_move_countdown: usize, // delays alien movement
+    wave: usize,
+    wave_countdown: usize, // delays the start of the next wave
+    ufo: Ufo,
+    ufo_popup: Option<UfoPopup>,
+    starting_lives: u8,
+    lives: u8,
+    next_extra_life: u64,
+    respawn_countdown: usize, // delays play resuming after the player is hit
+    sounds: Sounds,
+    muted: bool,
+    march_note: u8,
+    march_countdown: usize, // delays the next note of the marching bass
+    wave_aliens: usize, // formation size at the start of the wave
+    high_scores: HighScores,
+    high_scores_changed: bool, // the table needs saving
+    game_started: bool, // a player has started a game since the last take_game_started
+    initials: [u8; INITIALS_LEN],
+    initials_len: usize,
+    rng: Rng,
+    resume_status: Status, // where to return when unpaused
+    invincible: bool, // alien shots pass harmlessly, for debugging
+    frame_time: bool, // the view shows how long frames take to draw, for debugging
+    bindings: KeyBindings,
+    held: HeldKeys,
+    rebinding: Option<Action>, // waiting on the options screen for this action's new key
+    players: u8,
+    current_player: usize, // whose board is in play, from 0
+    waiting: Turn<WIDTH, HEIGHT>, // the other player's board in a two-player game
+    turn_countdown: usize, // delays play after the players swap
+    demo: bool, // the AI is playing to attract players
+    attract_countdown: usize // delays switching between the title screen and the demo
+}
+
+// Everything a player keeps between turns in a two-player game
+#[derive(Copy,Clone,Eq,PartialEq,Debug)]
+struct Turn<const WIDTH: usize, const HEIGHT: usize> {
+    cells: [[Cell; WIDTH]; HEIGHT],
+    player: Player,
+    aliens: Aliens,
+    ufo: Ufo,
+    alien_move_frequency: usize,
+    alien_shots: usize, // shots the formation may have in flight
+    wave_aliens: usize,
+    score: u64,
+    wave: usize,
+    lives: u8,
+    next_extra_life: u64
 }
 
 #[derive(Copy,Clone,Eq,PartialEq,Debug)]
 pub enum Status {
+    Title,
+    Options, // rebinding keys, reached from the title screen
     Normal,
+    Paused,
+    NextWave,
+    Dying,
+    NextPlayer, // the players are swapping turns
+    EnteringInitials, // the game is over with a score good enough for the table
     Over
 }
 
@@ -36,7 +138,23 @@ pub enum Status {
 #[repr(u8)]
 pub enum Cell {
     Empty,
-    Barrier
+    Barrier(u8), // a bunker cell with this many hits left
+    Wall // the board edge, which nothing wears down
+}
+
+impl Cell {
+    // Stops shots and the player
+    pub fn is_solid(&self) -> bool {
+        *self != Cell::Empty
+    }
+
+    fn damaged(&self) -> Cell {
+        match *self {
+            Cell::Barrier(health) if health > 1 => Cell::Barrier(health - 1),
+            Cell::Barrier(_) => Cell::Empty,
+            other => other
+        }
+    }
 }
 
 #[derive(Debug,Copy,Clone,Eq,PartialEq)]
@@ -80,7 +198,8 @@ pub struct Position {
 }
 
 impl Position {
-    pub fn is_legal(&self) -> bool {
+    // True if the position is on a board of the given size
+    pub fn is_legal<const WIDTH: usize, const HEIGHT: usize>(&self) -> bool {
         0 <= self.col && self.col < WIDTH as i16 && 0 <= self.row && self.row < HEIGHT as i16
     }
 
@@ -99,68 +218,114 @@ impl Position {
 }
 
 #[derive(Copy,Clone,Eq,PartialEq,Debug)]
-pub struct Shot {
+pub struct Player {
     pos: Position,
-    active: bool,
-    dir: Dir,
-    player_fired: bool // true if fired by player, false if by aliens
+    shots_fired: usize
 }
 
-impl Shot {
-    fn new(pos: Position) -> Self {Shot {pos, active: false, dir: Dir::N, player_fired: false}}
-
-    fn fire(&mut self, pos: Position, direction: Dir, player: bool) {
-        self.pos = pos;
-        self.active = true;
-        self.dir = direction;
-        self.player_fired = player
+impl Player {
+    fn new(pos: Position) -> Self {
+        Player {pos, shots_fired: 0}
     }
 
-    fn deactivate(&mut self) { self.active = false;}
-
-    fn fired_by_player(&self) -> bool {self.player_fired}
+    fn get_shot_position(&self) -> Position {
+        Position {row: self.pos.row - 1, col: self.pos.col}
+    }
 
-    fn next_pos(&self) -> Position {self.pos.neighbor(self.dir)}
+    pub fn pos(&self) -> Position {
+        self.pos
+    }
 
-    pub fn icon() -> char { '|' }
+    pub fn icon() -> char { '^' }
 }
 
-#[derive(Copy,Clone,Eq,PartialEq,Debug)]
-pub struct Player {
-    pos: Position,
-    active_shots: u8,
-    total_shots: u8
+#[derive(Debug,Copy,Clone,Eq,PartialEq)]
+pub enum AlienKind {
+    Squid,
+    Crab,
+    Octopus
 }
 
-impl Player {
-    fn new(pos: Position) -> Self {
-        Player {pos, active_shots: 0, total_shots: 3}
+impl AlienKind {
+    pub fn from_icon(icon: char) -> Option<AlienKind> {
+        match icon {
+            'Y' => Some(AlienKind::Squid),
+            'X' => Some(AlienKind::Crab),
+            '@' => Some(AlienKind::Octopus),
+            _ => None
+        }
+    }
+
+    pub fn icon(&self) -> char {
+        match self {
+            AlienKind::Squid => 'Y',
+            AlienKind::Crab => 'X',
+            AlienKind::Octopus => '@'
+        }
     }
 
-    fn can_fire_shot(&self) -> bool {
-        self.active_shots < self.total_shots
+    pub fn points(&self) -> u64 {
+        match self {
+            AlienKind::Squid => 30,
+            AlienKind::Crab => 20,
+            AlienKind::Octopus => 10
+        }
     }
 
-    fn get_shot_position(&self) -> Position {
-        Position {row: self.pos.row - 1, col: self.pos.col}
+    // Crabs are armoured and need two hits
+    fn hits(&self) -> u8 {
+        match self {
+            AlienKind::Crab => 2,
+            _ => 1
+        }
     }
 
-    pub fn icon() -> char { '^' }
+    // Updates the formation waits after this kind fires; squids fire rapidly
+    fn reload(&self) -> usize {
+        match self {
+            AlienKind::Squid => 0,
+            _ => 3
+        }
+    }
+
+    fn shot_kind(&self) -> ShotKind {
+        match self {
+            AlienKind::Squid => ShotKind::Plunger,
+            AlienKind::Crab => ShotKind::ZigZag,
+            AlienKind::Octopus => ShotKind::Straight
+        }
+    }
 }
 
 #[derive(Debug,Copy,Clone,Eq,PartialEq)]
 pub struct Alien {
     pos: Position,
-    alive: bool
+    alive: bool,
+    kind: AlienKind,
+    hits: u8 // hits left before it is destroyed
 }
 
 impl Alien {
-    fn new(pos: Position) -> Self {
-        Alien {pos, alive: true}
+    fn new(pos: Position, kind: AlienKind) -> Self {
+        Alien {pos, alive: true, kind, hits: kind.hits()}
     }
 
-    fn directly_above_player(&self, player: &Player) -> bool {
-        player.pos.col == self.pos.col
+    pub fn kind(&self) -> AlienKind {
+        self.kind
+    }
+
+    pub fn pos(&self) -> Position {
+        self.pos
+    }
+
+    pub fn damaged(&self) -> bool {
+        self.hits < self.kind.hits()
+    }
+
+    // Columns near the player are likelier to fire
+    fn fire_weight(&self, player: &Player) -> usize {
+        let distance = (player.pos.col - self.pos.col).unsigned_abs() as usize;
+        1 + FIRE_AIM_BIAS * FIRE_AIM_RANGE.saturating_sub(distance)
     }
 
     fn get_shot_pos(&self) -> Position {
@@ -168,115 +333,677 @@ impl Alien {
         Position {row: (row + 1) as i16, col: col as i16}
     }
 
-    pub fn icon() -> char {'@'}
+    pub fn icon(&self) -> char {
+        self.kind.icon()
+    }
 }
 
 #[derive(Debug,Copy,Clone,Eq,PartialEq)]
 pub struct Aliens {
-    aliens: [[Alien; 24]; 5],
-    bottom_row: u32,  // lowest row of aliens
-    active_shots: u8,
-    total_shots: u8,
+    aliens: [Alien; MAX_ALIENS],
+    fire_countdown: usize, // delays the next alien shot
     dir: Dir,
 }
 
 impl Aliens {
     fn new() -> Self {
-        Aliens {aliens: [[Alien::new(Position {row: 0, col: 0}); 24]; 5], bottom_row: 4, active_shots: 0, total_shots: 1, dir: Dir::E}
+        let placeholder = Alien {pos: Position {row: 0, col: 0}, alive: false, kind: AlienKind::Octopus, hits: 0};
+        Aliens {aliens: [placeholder; MAX_ALIENS], fire_countdown: 0, dir: Dir::E}
     }
 
-    fn can_fire_shot(&self) -> bool {
-        self.active_shots < self.total_shots
+    fn live(&self) -> impl Iterator<Item=&Alien> {
+        self.aliens.iter().filter(|alien| alien.alive)
     }
 
-    fn find_alien_to_fire(&self, player: &Player) -> Option<&Alien> {
-        for row in self.aliens.iter() {
-            for alien in row.iter() {
-                if alien.directly_above_player(player) {
-                    return Some(alien)
-                }
+    fn live_mut(&mut self) -> impl Iterator<Item=&mut Alien> {
+        self.aliens.iter_mut().filter(|alien| alien.alive)
+    }
+
+    fn all_destroyed(&self) -> bool {
+        self.live().next().is_none()
+    }
+
+    // Top-left and bottom-right corners of the live formation. It shrinks as
+    // edge columns are cleared, letting the survivors travel further.
+    fn bounds(&self) -> Option<(Position, Position)> {
+        self.live().fold(None, |bounds, alien| {
+            let p = alien.pos;
+            Some(match bounds {
+                None => (p, p),
+                Some((min, max)) => (
+                    Position {row: min.row.min(p.row), col: min.col.min(p.col)},
+                    Position {row: max.row.max(p.row), col: max.col.max(p.col)}
+                )
+            })
+        })
+    }
+
+    fn will_hit_wall<const WIDTH: usize, const HEIGHT: usize>(&self) -> bool {
+        match self.bounds() {
+            Some((min, max)) => !min.neighbor(self.dir).is_legal::<WIDTH, HEIGHT>()
+                || !max.neighbor(self.dir).is_legal::<WIDTH, HEIGHT>(),
+            None => false
+        }
+    }
+
+    // Steps the formation sideways, or down and turned around at a wall
+    fn advance<const WIDTH: usize, const HEIGHT: usize>(&mut self) {
+        let step = if self.will_hit_wall::<WIDTH, HEIGHT>() {
+            self.dir = self.dir.reverse();
+            Dir::S
+        } else {
+            self.dir
+        };
+        for alien in self.live_mut() {
+            alien.pos = alien.pos.neighbor(step);
+        }
+    }
+
+    fn reached_row(&self, row: i16) -> bool {
+        self.bounds().is_some_and(|(_, max)| max.row >= row)
+    }
+
+    fn ready_to_fire(&self) -> bool {
+        self.fire_countdown == 0
+    }
+
+    fn reload(&mut self) {
+        if self.fire_countdown > 0 {
+            self.fire_countdown -= 1;
+        }
+    }
+
+    // Only the lowest live alien in a column has a clear line of fire; one of
+    // those is picked at random, weighted toward the player
+    fn find_alien_to_fire<const WIDTH: usize>(&self, player: &Player, rng: &mut Rng) -> Option<&Alien> {
+        let mut lowest: [Option<&Alien>; WIDTH] = [None; WIDTH];
+        for alien in self.live() {
+            let col = alien.pos.col as usize;
+            if lowest[col].is_none_or(|other| other.pos.row < alien.pos.row) {
+                lowest[col] = Some(alien);
             }
         }
+        let total: usize = lowest.iter().flatten().map(|alien| alien.fire_weight(player)).sum();
+        if total == 0 {
+            return None
+        }
+        let mut pick = rng.below(total);
+        for alien in lowest.iter().flatten() {
+            let weight = alien.fire_weight(player);
+            if pick < weight {
+                return Some(alien)
+            }
+            pick -= weight;
+        }
         None
     }
+}
+
+// The mystery ship: crosses the top row every so often for bonus points
+#[derive(Debug,Copy,Clone,Eq,PartialEq)]
+pub struct Ufo {
+    pos: Position, // leftmost cell
+    active: bool,
+    dir: Dir,
+    countdown: usize // delays the next fly-by
+}
+
+impl Ufo {
+    fn new() -> Self {
+        Ufo {pos: Position {row: 0, col: 0}, active: false, dir: Dir::E, countdown: UFO_INTERVAL}
+    }
+
+    fn launch<const WIDTH: usize>(&mut self, dir: Dir) {
+        let col = match dir {
+            Dir::W => WIDTH as i16 - UFO_WIDTH,
+            _ => 0
+        };
+        self.pos = Position {row: 0, col};
+        self.dir = dir;
+        self.active = true;
+        self.countdown = UFO_INTERVAL;
+    }
+
+    fn advance<const WIDTH: usize, const HEIGHT: usize>(&mut self) {
+        let next = self.pos.neighbor(self.dir);
+        let right = Position {row: next.row, col: next.col + UFO_WIDTH - 1};
+        if next.is_legal::<WIDTH, HEIGHT>() && right.is_legal::<WIDTH, HEIGHT>() {
+            self.pos = next;
+        } else {
+            self.active = false;
+        }
+    }
+
+    fn occupies(&self, p: Position) -> bool {
+        self.active && p.row == self.pos.row && self.pos.col <= p.col && p.col < self.pos.col + UFO_WIDTH
+    }
+
+    pub fn icon_at(&self, p: Position) -> Option<char> {
+        if self.occupies(p) {
+            Some(['<', 'O', '>'][(p.col - self.pos.col) as usize])
+        } else {
+            None
+        }
+    }
+}
+
+// Bonus shown above the spot where the mystery ship was destroyed
+#[derive(Debug,Copy,Clone,Eq,PartialEq)]
+pub struct UfoPopup {
+    col: usize,
+    points: u64,
+    countdown: usize
+}
+
+impl UfoPopup {
+    pub fn col(&self) -> usize {
+        self.col
+    }
+
+    pub fn points(&self) -> u64 {
+        self.points
+    }
+}
+
+// Sound effects the game asks for. game_core never touches the speaker; the
+// kernel drains these each tick and plays them.
+#[derive(Debug,Copy,Clone,Eq,PartialEq)]
+pub enum Sound {
+    March(u8), // one of the four notes of the marching bass
+    Shot,
+    AlienExplosion,
+    Ufo,
+    PlayerDeath
+}
+
+#[derive(Debug,Copy,Clone,Eq,PartialEq)]
+pub struct Sounds {
+    sounds: [Sound; MAX_SOUNDS],
+    len: usize
+}
+
+impl Sounds {
+    fn new() -> Self {
+        Sounds {sounds: [Sound::Shot; MAX_SOUNDS], len: 0}
+    }
 
-    // fn move_aliens(&self) {
-    //     for (row_num, row) in self.aliens.aliens.iter().enumerate() {
-    //         for (col_num, alien) in row.iter().enumerate() {
-    //             if alien.alive && alien.pos == p {
-    //
-    //                 return Some((row_num, col_num, alien))
-    //             }
-    //         }
-    //     }
-    // }
+    // Sounds past the limit are dropped; nobody can hear eight at once anyway
+    fn push(&mut self, sound: Sound) {
+        if self.len < MAX_SOUNDS {
+            self.sounds[self.len] = sound;
+            self.len += 1;
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item=&Sound> {
+        self.sounds[..self.len].iter()
+    }
+}
+
+// Whatever stands on a board cell, in the order the screen shows them: the
+// player over the mystery ship over an alien over a shot
+#[derive(Debug,Copy,Clone,Eq,PartialEq)]
+pub enum EntityRef<'a> {
+    Player(&'a Player),
+    Ufo(&'a Ufo),
+    Alien(usize, &'a Alien),
+    Shot(&'a Shot)
 }
 
-const LAST_RAW : &'static i32 = &16;
-
-const START: &'static str =
-"#..............................................................................#
-#..@..@..@..@..@..@..@..@..@..@..@..@..@..@..@..@..@..@..@..@..@..@..@..@......#
-#...@..@..@..@..@..@..@..@..@..@..@..@..@..@..@..@..@..@..@..@..@..@..@..@.....#
-#....@..@..@..@..@..@..@..@..@..@..@..@..@..@..@..@..@..@..@..@..@..@..@..@....#
-#...@..@..@..@..@..@..@..@..@..@..@..@..@..@..@..@..@..@..@..@..@..@..@..@.....#
-#..@..@..@..@..@..@..@..@..@..@..@..@..@..@..@..@..@..@..@..@..@..@..@..@......#
-#..............................................................................#
-#..............................................................................#
-#..............................................................................#
-#..............................................................................#
-#..............................................................................#
-#..............................................................................#
-#..............................................................................#
-#..............................................................................#
-#..............................................................................#
-#..............................................................................#
-#..............................................................................#
-###..###..###..###..###..###..###..###..###..###..###..###..###..###..###..##.##
-###..###..###..###..###..###..###..###..###..###..###..###..###..###..###..##.##
-###..###..###..###..###..###..###..###..###..###..###..###..###..###..###..##.##
-#..............................................................................#
-#...................................^..........................................#
-#..............................................................................#";
-
-impl SpaceInvadersGame {
+impl<const WIDTH: usize, const HEIGHT: usize> Default for SpaceInvadersGame<WIDTH, HEIGHT> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const WIDTH: usize, const HEIGHT: usize> SpaceInvadersGame<WIDTH, HEIGHT> {
     pub fn new() -> Self {
+        SpaceInvadersGame::with_lives(STARTING_LIVES)
+    }
+
+    pub fn with_lives(starting_lives: u8) -> Self {
+        SpaceInvadersGame::build(&LEVELS, starting_lives)
+    }
+
+    // A game played through its own levels, e.g. ones drawn for a smaller
+    // board. Every level is checked here, so a bad one is an error now
+    // rather than a panic when its wave comes round.
+    pub fn with_levels(levels: &'static [&'static str]) -> Result<Self, LevelError> {
+        if levels.is_empty() {
+            return Err(LevelError {row: 0, col: 0, kind: LevelErrorKind::NoLevels})
+        }
+        for level in levels.iter() {
+            Level::<WIDTH, HEIGHT>::parse(level)?;
+        }
+        Ok(SpaceInvadersGame::build(levels, STARTING_LIVES))
+    }
+
+    fn build(levels: &'static [&'static str], starting_lives: u8) -> Self {
         let mut game = SpaceInvadersGame {
-          cells: [[Cell::Empty; WIDTH]; HEIGHT],
+            levels,
+            cells: [[Cell::Empty; WIDTH]; HEIGHT],
             status: Status::Normal,
             player: Player::new(Position {row: 0, col: 0}),
             aliens: Aliens::new(),
-            shots: [Shot::new(Position {row: 0, col: 0}); 4],
+            player_shots: ShotPool::new(PLAYER_SHOTS),
+            alien_shots: ShotPool::new(1),
+            occupancy: Occupancy::new(),
             score: 0,
             last_dir: None,
             countdown: 0,
             fired_shot: false,
             alien_move_countdown: 0,
-            alien_move_frequency: 3
+            alien_move_frequency: 0,
+            wave: 1,
+            wave_countdown: 0,
+            ufo: Ufo::new(),
+            ufo_popup: None,
+            starting_lives,
+            lives: starting_lives,
+            next_extra_life: EXTRA_LIFE_SCORE,
+            respawn_countdown: 0,
+            resume_status: Status::Normal,
+            sounds: Sounds::new(),
+            muted: false,
+            march_note: 0,
+            march_countdown: 0,
+            wave_aliens: 0,
+            high_scores: HighScores::new(),
+            high_scores_changed: false,
+            game_started: false,
+            initials: [b' '; INITIALS_LEN],
+            initials_len: 0,
+            rng: Rng::default(),
+            invincible: false,
+            frame_time: false,
+            bindings: KeyBindings::default(),
+            held: HeldKeys::default(),
+            rebinding: None,
+            players: 1,
+            current_player: 0,
+            waiting: Turn {
+                cells: [[Cell::Empty; WIDTH]; HEIGHT],
+                player: Player::new(Position {row: 0, col: 0}),
+                aliens: Aliens::new(),
+                ufo: Ufo::new(),
+                alien_move_frequency: 0,
+                alien_shots: 0,
+                wave_aliens: 0,
+                score: 0,
+                wave: 1,
+                lives: 0,
+                next_extra_life: EXTRA_LIFE_SCORE
+            },
+            turn_countdown: 0,
+            demo: false,
+            attract_countdown: TITLE_TIME
         };
         game.reset();
+        game.status = Status::Title;
         game
     }
 
     pub fn update(&mut self) {
-        if self.status == Status::Normal{
-            self.move_player();
-            self.last_dir = None;
-            if self.fired_shot {
-                self.fired_shot = false;
-                self.player_shoot();
+        if self.demo {
+            self.steer_demo();
+        }
+        match self.status {
+            Status::Title => {
+                if self.attract_countdown == 0 {
+                    self.start_demo();
+                } else {
+                    self.attract_countdown -= 1;
+                }
             }
-            if self.alien_countdown_complete() {
-                self.move_aliens();
+            Status::Normal => self.update_normal(),
+            Status::NextWave => {
+                if self.wave_countdown == 0 {
+                    self.next_screen();
+                } else {
+                    self.wave_countdown -= 1;
+                }
+            }
+            Status::Dying => {
+                if self.respawn_countdown == 0 {
+                    if self.lives == 0 {
+                        self.game_over();
+                    } else if self.other_player_waiting() {
+                        self.swap_players();
+                    } else {
+                        self.status = Status::Normal;
+                    }
+                } else {
+                    self.respawn_countdown -= 1;
+                }
+            }
+            Status::NextPlayer => {
+                if self.turn_countdown == 0 {
+                    self.status = Status::Normal;
+                } else {
+                    self.turn_countdown -= 1;
+                }
+            }
+            Status::Options | Status::Paused | Status::EnteringInitials | Status::Over => {}
+        }
+    }
+
+    // Stops as soon as a phase ends the turn, so a game that is already over
+    // can't also lose a life to a bomb still in flight
+    fn update_normal(&mut self) {
+        self.move_player();
+        self.last_dir = None;
+        if self.fired_shot {
+            self.fired_shot = false;
+            self.player_shoot();
+        }
+        if self.alien_countdown_complete() {
+            self.move_aliens();
+            if self.status != Status::Normal {
+                return
+            }
+        }
+        self.march();
+        self.move_ufo();
+        self.aliens.reload();
+        self.alien_shoot();
+        self.move_shots();
+        if self.status != Status::Normal {
+            return
+        }
+        if self.aliens.all_destroyed() {
+            self.wave += 1;
+            self.wave_countdown = WAVE_PAUSE;
+            self.status = Status::NextWave;
+        }
+    }
+
+    // The current player is out of lives. In a two-player game the other
+    // player plays on once any initials are in.
+    fn game_over(&mut self) {
+        if self.demo {
+            self.end_demo();
+        } else if self.high_scores.rank(self.score).is_some() {
+            self.initials_len = 0;
+            self.status = Status::EnteringInitials;
+        } else {
+            self.next_player_or_over();
+        }
+    }
+
+    // The attract mode: a one-player game the AI plays until time runs out,
+    // it loses or somebody presses a key
+    fn start_demo(&mut self) {
+        self.start(1);
+        self.game_started = false;
+        self.demo = true;
+        self.attract_countdown = DEMO_TIME;
+    }
+
+    fn end_demo(&mut self) {
+        self.demo = false;
+        self.status = Status::Title;
+        self.attract_countdown = TITLE_TIME;
+    }
+
+    fn steer_demo(&mut self) {
+        if self.attract_countdown == 0 {
+            self.end_demo();
+            return
+        }
+        self.attract_countdown -= 1;
+        match ai::choose(self) {
+            Some(Action::MoveLeft) => self.last_dir = Some(Dir::W),
+            Some(Action::MoveRight) => self.last_dir = Some(Dir::E),
+            Some(Action::Fire) => self.fired_shot = true,
+            _ => {}
+        }
+    }
+
+    // True while the attract-mode demo is playing
+    pub fn demo(&self) -> bool {
+        self.demo
+    }
+
+    fn next_player_or_over(&mut self) {
+        if self.other_player_waiting() {
+            self.swap_players();
+        } else {
+            self.status = Status::Over;
+        }
+    }
+
+    fn other_player_waiting(&self) -> bool {
+        self.players == 2 && self.waiting.lives > 0
+    }
+
+    // Puts the current board aside and brings back the other player's
+    fn swap_players(&mut self) {
+        let waiting = self.waiting;
+        self.waiting = self.save_turn();
+        self.restore_turn(&waiting);
+        self.current_player = 1 - self.current_player;
+        self.turn_countdown = TURN_PAUSE;
+        self.status = Status::NextPlayer;
+    }
+
+    fn save_turn(&self) -> Turn<WIDTH, HEIGHT> {
+        Turn {
+            cells: self.cells,
+            player: self.player,
+            aliens: self.aliens,
+            ufo: self.ufo,
+            alien_move_frequency: self.alien_move_frequency,
+            alien_shots: self.alien_shots.capacity(),
+            wave_aliens: self.wave_aliens,
+            score: self.score,
+            wave: self.wave,
+            lives: self.lives,
+            next_extra_life: self.next_extra_life
+        }
+    }
+
+    // Shots, the bonus popup and the march all start afresh with the turn
+    fn restore_turn(&mut self, turn: &Turn<WIDTH, HEIGHT>) {
+        self.cells = turn.cells;
+        self.player = turn.player;
+        self.aliens = turn.aliens;
+        self.occupancy = Occupancy::new();
+        self.occupancy.place_aliens(&self.aliens);
+        self.ufo = turn.ufo;
+        self.ufo_popup = None;
+        self.alien_move_frequency = turn.alien_move_frequency;
+        self.alien_move_countdown = turn.alien_move_frequency;
+        self.alien_shots.set_capacity(turn.alien_shots);
+        self.clear_shots();
+        self.wave_aliens = turn.wave_aliens;
+        self.score = turn.score;
+        self.wave = turn.wave;
+        self.lives = turn.lives;
+        self.next_extra_life = turn.next_extra_life;
+        self.march_note = 0;
+        self.march_countdown = 0;
+        self.last_dir = None;
+        self.fired_shot = false;
+    }
+
+    // Letters fill in the initials, backspace takes one back and enter puts
+    // the finished initials in the table. Confirm only counts once the
+    // initials are complete, so a letter bound to it can still be typed.
+    fn enter_initial(&mut self, key: DecodedKey) {
+        match normalize(key) {
+            k if self.initials_len == INITIALS_LEN && self.bindings.is(k, Action::Confirm) => {
+                self.high_scores.insert(self.initials, self.score);
+                self.high_scores_changed = true;
+                self.next_player_or_over();
+            }
+            DecodedKey::Unicode(c) if c.is_ascii_alphabetic() && self.initials_len < INITIALS_LEN => {
+                self.initials[self.initials_len] = c.to_ascii_uppercase() as u8;
+                self.initials_len += 1;
+            }
+            DecodedKey::Unicode('\u{8}') => {
+                self.initials_len = self.initials_len.saturating_sub(1);
+            }
+            _ => {}
+        }
+    }
+
+    // A digit picks an action to rebind and the next key becomes its key,
+    // unless it is Escape; a preset's letter replaces every binding and
+    // Escape or Enter goes back to the title screen
+    fn options_key(&mut self, key: DecodedKey) {
+        let key = normalize(key);
+        let escape = DecodedKey::Unicode('\u{1b}');
+        if let Some(action) = self.rebinding.take() {
+            if key != escape {
+                self.bindings.bind(action, key);
+            }
+            return
+        }
+        match key {
+            DecodedKey::Unicode('\u{1b}') | DecodedKey::Unicode('\n') => self.status = Status::Title,
+            DecodedKey::Unicode(c) => {
+                if let Some(action) = ACTIONS.iter().find(|action| action.menu_key() == c) {
+                    self.rebinding = Some(*action);
+                } else if let Some(preset) = PRESETS.iter().find(|preset| preset.menu_key() == c) {
+                    self.bindings = KeyBindings::preset(*preset);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    pub fn bindings(&self) -> &KeyBindings {
+        &self.bindings
+    }
+
+    // The action whose new key the options screen is waiting for
+    pub fn rebinding(&self) -> Option<Action> {
+        self.rebinding
+    }
+
+    // Seeds alien fire; the game is otherwise entirely determined by the keys
+    pub fn seed(&mut self, seed: u64) {
+        self.rng = Rng::new(seed);
+    }
+
+    pub fn high_scores(&self) -> &HighScores {
+        &self.high_scores
+    }
+
+    // Replaces the table, e.g. with the one saved on the last boot
+    pub fn set_high_scores(&mut self, high_scores: HighScores) {
+        self.high_scores = high_scores;
+    }
+
+    // The table, once, after each change to it
+    pub fn take_high_scores_to_save(&mut self) -> Option<HighScores> {
+        if self.high_scores_changed {
+            self.high_scores_changed = false;
+            Some(self.high_scores)
+        } else {
+            None
+        }
+    }
+
+    // The initials typed so far
+    pub fn initials(&self) -> &str {
+        core::str::from_utf8(&self.initials[..self.initials_len]).unwrap_or("")
+    }
+
+    fn pause(&mut self) {
+        self.resume_status = self.status;
+        self.status = Status::Paused;
+    }
+
+    fn resume(&mut self) {
+        self.status = self.resume_status;
+    }
+
+    // Loses a life and freezes the board while the player respawns
+    fn player_hit(&mut self) {
+        self.play(Sound::PlayerDeath);
+        self.lives -= 1;
+        self.respawn_countdown = RESPAWN_TIME;
+        self.status = Status::Dying;
+        self.clear_shots();
+        self.last_dir = None;
+        self.fired_shot = false;
+    }
+
+    fn clear_shots(&mut self) {
+        self.player_shots.clear();
+        self.alien_shots.clear();
+        self.place_shots();
+    }
+
+    fn place_shots(&mut self) {
+        self.occupancy.place_shots(&self.player_shots, &self.alien_shots);
+    }
+
+    // Scores stop at u64::MAX, as does next_extra_life once no more extra
+    // lives are to be had
+    fn add_score(&mut self, points: u64) {
+        self.score = self.score.saturating_add(points);
+        while self.score >= self.next_extra_life && self.next_extra_life < u64::MAX {
+            self.lives = (self.lives + 1).min(MAX_LIVES);
+            self.next_extra_life = self.next_extra_life.saturating_add(EXTRA_LIFE_SCORE);
+        }
+    }
+
+    // The demo plays silently
+    fn play(&mut self, sound: Sound) {
+        if !self.muted && !self.demo {
+            self.sounds.push(sound);
+        }
+    }
+
+    // Hands over the sounds queued since the last call
+    pub fn take_sounds(&mut self) -> Sounds {
+        core::mem::replace(&mut self.sounds, Sounds::new())
+    }
+
+    pub fn muted(&self) -> bool {
+        self.muted
+    }
+
+    // The marching bass plays faster the fewer aliens are left
+    fn march(&mut self) {
+        if self.march_countdown == 0 {
+            self.play(Sound::March(self.march_note));
+            self.march_note = (self.march_note + 1) % MARCH_NOTES;
+            self.march_countdown = self.march_interval();
+        } else {
+            self.march_countdown -= 1;
+        }
+    }
+
+    fn march_interval(&self) -> usize {
+        let live = self.aliens.live().count();
+        MARCH_FASTEST + (MARCH_SLOWEST - MARCH_FASTEST) * live.saturating_sub(1) / self.wave_aliens.saturating_sub(1).max(1)
+    }
+
+    fn move_ufo(&mut self) {
+        if self.ufo.active {
+            self.play(Sound::Ufo);
+            self.ufo.advance::<WIDTH, HEIGHT>();
+        } else if self.ufo.countdown == 0 {
+            // Like the arcade, the shot count decides which side it enters from
+            let dir = match self.player.shots_fired % 2 {0 => Dir::E, _ => Dir::W};
+            self.ufo.launch::<WIDTH>(dir);
+        } else {
+            self.ufo.countdown -= 1;
+        }
+        if let Some(popup) = self.ufo_popup.as_mut() {
+            if popup.countdown == 0 {
+                self.ufo_popup = None;
+            } else {
+                popup.countdown -= 1;
             }
-            self.alien_shoot();
-            self.move_shots();
-            self.check_collisions();
         }
     }
 
     fn alien_countdown_complete(&mut self) -> bool {
-        if self.alien_move_countdown <= 0 {
+        if self.alien_move_countdown == 0 {
             self.alien_move_countdown = self.alien_move_frequency;
             true
         } else {
@@ -293,9 +1020,129 @@ impl SpaceInvadersGame {
         self.score
     }
 
+    pub fn wave(&self) -> usize {
+        self.wave
+    }
+
+    pub fn lives(&self) -> u8 {
+        self.lives
+    }
+
+    // 1, or 2 in a two-player game
+    pub fn players(&self) -> u8 {
+        self.players
+    }
+
+    // Whose turn it is, from 0
+    pub fn current_player(&self) -> usize {
+        self.current_player
+    }
+
+    // Either player's score, whether or not it is their turn
+    pub fn player_score(&self, player: usize) -> u64 {
+        if player == self.current_player {self.score} else {self.waiting.score}
+    }
+
+    pub fn player_exploding(&self) -> bool {
+        self.status == Status::Dying && self.respawn_countdown > RESPAWN_TIME - EXPLOSION_TIME
+    }
+
+    pub fn respawn_countdown(&self) -> usize {
+        self.respawn_countdown
+    }
+
+    pub fn player(&self) -> &Player {
+        &self.player
+    }
+
+    pub fn ufo(&self) -> &Ufo {
+        &self.ufo
+    }
+
+    pub fn ufo_popup(&self) -> Option<UfoPopup> {
+        self.ufo_popup
+    }
+
+    // Debugging hooks for the serial console
+
+    pub fn set_score(&mut self, score: u64) {
+        self.score = score;
+        self.next_extra_life = (score / EXTRA_LIFE_SCORE + 1).saturating_mul(EXTRA_LIFE_SCORE);
+    }
+
+    // Starts the given wave afresh, keeping score and lives
+    pub fn set_wave(&mut self, wave: usize) {
+        self.wave = wave.max(1);
+        self.set_up_game();
+    }
+
+    pub fn invincible(&self) -> bool {
+        self.invincible
+    }
+
+    pub fn set_invincible(&mut self, invincible: bool) {
+        self.invincible = invincible;
+    }
+
+    pub fn frame_time(&self) -> bool {
+        self.frame_time
+    }
+
+    pub fn set_frame_time(&mut self, frame_time: bool) {
+        self.frame_time = frame_time;
+    }
+
+    // Sends the mystery ship across now, unless it is already flying
+    pub fn spawn_ufo(&mut self) {
+        if !self.ufo.active {
+            let dir = match self.player.shots_fired % 2 {0 => Dir::E, _ => Dir::W};
+            self.ufo.launch::<WIDTH>(dir);
+        }
+    }
+
+    pub fn alien_move_frequency(&self) -> usize {
+        self.alien_move_frequency
+    }
+
+    pub fn set_alien_move_frequency(&mut self, frequency: usize) {
+        self.alien_move_frequency = frequency;
+        self.alien_move_countdown = self.alien_move_countdown.min(frequency);
+    }
+
+    // Runs a single update of a paused game and pauses it again, unless the
+    // update ended the game
+    pub fn step_paused(&mut self) {
+        if self.status != Status::Paused {
+            return
+        }
+        self.resume();
+        self.update();
+        if let Status::Normal | Status::NextWave | Status::Dying | Status::NextPlayer = self.status {
+            self.pause();
+        }
+    }
+
+    // Both players start on the same fresh board
     fn reset(&mut self) {
+        self.wave = 1;
         self.set_up_game();
         self.score = 0;
+        self.lives = self.starting_lives;
+        self.next_extra_life = EXTRA_LIFE_SCORE;
+        self.current_player = 0;
+        self.waiting = self.save_turn();
+    }
+
+    fn start(&mut self, players: u8) {
+        self.players = players;
+        self.reset();
+        self.game_started = true;
+    }
+
+    // True, once, after a player starts a game, so the kernel can begin a
+    // fresh recording of it
+    pub fn take_game_started(&mut self) -> bool {
+        core::mem::take(&mut self.game_started)
     }
 
     // Brings new aliens while keeping score
@@ -304,259 +1151,357 @@ impl SpaceInvadersGame {
     }
 
     fn set_up_game(&mut self) {
-        let mut alien_col = 0;
-        let mut alien_row = 0;
-        for (row_num, row_chars) in START.split("\n").enumerate() {
-            for (col_num, icon) in row_chars.chars().enumerate() {
-                self.translate_icon(&mut alien_row, &mut alien_col, row_num, col_num, icon)
-            }
-        }
-        for shot in self.shots.iter_mut() {
-            shot.active = false;
-        }
+        self.load_level(&Level::for_wave(self.levels, self.wave));
+    }
+
+    fn load_level(&mut self, level: &Level<WIDTH, HEIGHT>) {
+        self.cells = level.cells;
+        self.player = Player::new(level.player);
+        self.aliens = level.aliens;
+        self.occupancy = Occupancy::new();
+        self.alien_move_frequency = level.alien_move_frequency;
+        self.ufo = Ufo::new();
+        self.ufo_popup = None;
+        self.clear_shots();
+        self.alien_shots.set_capacity(level.alien_shots);
+        self.apply_wave_difficulty();
+        self.wave_aliens = self.aliens.live().count();
+        self.march_note = 0;
+        self.march_countdown = 0;
         self.status = Status::Normal;
         self.last_dir = None;
         self.fired_shot = false;
     }
 
-    fn translate_icon(&mut self, alien_row: &mut usize, alien_col: &mut usize, row: usize, col: usize, icon: char) {
-        match icon {
-            '.' => self.cells[row][col] = Cell::Empty,
-            '#' => self.cells[row][col] = Cell::Barrier,
-            '^' => self.player = Player::new(Position {row: row as i16, col: col as i16}),
-            '@' => {
-                self.aliens.aliens[*alien_row][*alien_col] = Alien::new(
-                    Position {row: row as i16, col: col as i16}
-                );
-                *alien_col += 1;
-                if *alien_col >= self.aliens.aliens[0].len() {
-                    *alien_col = 0;
-                    *alien_row += 1;
-                }
-            }
-            _ => panic!("Invalid char '{}'", icon)
+    // Each pass through the level table moves faster and shoots more than
+    // the last, and each wave's formation starts a row closer to the player
+    // than the one before, up to MAX_WAVE_DROP rows below its level position
+    fn apply_wave_difficulty(&mut self) {
+        let lap = (self.wave - 1) / self.levels.len();
+        self.alien_move_frequency = self.alien_move_frequency.saturating_sub(lap);
+        self.alien_move_countdown = self.alien_move_frequency;
+        self.alien_shots.set_capacity(self.alien_shots.capacity() + lap);
+        for alien in self.aliens.live_mut() {
+            alien.pos.row += (self.wave - 1).min(MAX_WAVE_DROP) as i16;
         }
+        self.occupancy.place_aliens(&self.aliens);
+    }
+
+    // True if the position is on this game's board
+    pub fn is_legal(&self, p: Position) -> bool {
+        p.is_legal::<WIDTH, HEIGHT>()
     }
 
     pub fn cell(&self, p: Position) -> Cell {
         self.cells[p.row as usize][p.col as usize]
     }
 
-    pub fn cell_pos_iter(&self) -> RowColIter {
+    pub fn cell_pos_iter(&self) -> RowColIter<WIDTH, HEIGHT> {
         RowColIter { row: 0, col: 0 }
     }
 
+    pub fn live_aliens(&self) -> impl Iterator<Item=&Alien> {
+        self.aliens.live()
+    }
+
+    // Shots in flight from both sides
+    pub fn shots(&self) -> impl Iterator<Item=&Shot> {
+        self.player_shots.active().chain(self.alien_shots.active())
+    }
+
     pub fn player_at(&self, p: Position) -> bool {
         p == self.player.pos
     }
 
-    pub fn alien_at(&self, p: Position) -> Option<(usize,usize,&Alien)> {
-        for (row_num, row) in self.aliens.aliens.iter().enumerate() {
-            for (col_num, alien) in row.iter().enumerate() {
-                if alien.alive && alien.pos == p {
-                    return Some((row_num, col_num, alien))
-                }
-            }
+    pub fn alien_at(&self, p: Position) -> Option<(usize,&Alien)> {
+        self.occupancy.alien_at(p).map(|i| (i, &self.aliens.aliens[i]))
+    }
+
+    pub fn entity_at(&self, p: Position) -> Option<EntityRef<'_>> {
+        if self.player_at(p) {
+            Some(EntityRef::Player(&self.player))
+        } else if self.ufo.occupies(p) {
+            Some(EntityRef::Ufo(&self.ufo))
+        } else if let Some((i, alien)) = self.alien_at(p) {
+            Some(EntityRef::Alien(i, alien))
+        } else {
+            self.shot_at(p).map(EntityRef::Shot)
         }
-        return None
     }
 
     pub fn move_aliens(&mut self) {
-        let mut will_hit_wall = false;
-        for i in 0..self.aliens.aliens.len(){
-            for j in 0..self.aliens.aliens.get(0).unwrap().len(){
-                let mut alien1 : &mut Alien = &mut self.aliens.aliens[i][j];
-                let nextPos  = alien1.pos.neighbor(self.aliens.dir);
-                let down_next = alien1.pos.neighbor(Dir::S);
-                let(row, col) = nextPos.row_col();
-                if !nextPos.is_legal(){
-                    will_hit_wall = true;
-                }
+        self.aliens.advance::<WIDTH, HEIGHT>();
+        self.occupancy.place_aliens(&self.aliens);
+        // Bunkers in the formation's way are crushed as it descends
+        for alien in self.aliens.live() {
+            let (row, col) = alien.pos.row_col();
+            if let Cell::Barrier(_) = self.cells[row][col] {
+                self.cells[row][col] = Cell::Empty;
             }
         }
-
-        if will_hit_wall {
-            self.aliens.dir = self.aliens.dir.reverse();
-        }
-
-        for i in 0..self.aliens.aliens.len(){
-            for j in 0..self.aliens.aliens.get(0).unwrap().len(){
-                let mut alien1 : &mut Alien = &mut self.aliens.aliens[i][j];
-                let nextPos  = alien1.pos.neighbor(self.aliens.dir);
-                let down_next = alien1.pos.neighbor(Dir::S);
-                let(row, col) = nextPos.row_col();
-                if row as i32 == *LAST_RAW {
-                    self.status = Status::Over;
-                }else{
-                    if will_hit_wall{
-                        alien1.pos = down_next ;
-                    }else{
-                        alien1.pos = nextPos;
-                    }
-                }
-            }
+        // An invaded board can't be played on, so the player is out whatever
+        // lives are left
+        if self.aliens.reached_row(HEIGHT.saturating_sub(INVASION_HEIGHT) as i16) {
+            self.lives = 0;
+            self.game_over();
         }
-
     }
 
-    pub fn shot_at(&self, p: Position) -> bool {
-        for shot in self.shots.iter() {
-            if shot.active && shot.pos == p {
-                return true
-            }
-        }
-        return false
+    pub fn shot_at(&self, p: Position) -> Option<&Shot> {
+        self.occupancy.shot_at(p).map(|id| match id {
+            ShotId::Player(i) => &self.player_shots.shots[i as usize],
+            ShotId::Alien(i) => &self.alien_shots.shots[i as usize]
+        })
     }
 
+    // A tapped key moves a column; a held one keeps moving a column an update
     fn move_player(&mut self) {
-        if let Some(dir) = self.last_dir {
+        if let Some(dir) = self.last_dir.or_else(|| self.held_dir()) {
             let neighbor = self.player.pos.neighbor(dir);
-            if neighbor.is_legal() {
+            if neighbor.is_legal::<WIDTH, HEIGHT>() {
                 let (row, col) = neighbor.row_col();
-                if self.cells[row][col] != Cell::Barrier {
+                if !self.cells[row][col].is_solid() {
                     self.player.pos = neighbor;
                 }
             }
         }
     }
 
+    fn held_dir(&self) -> Option<Dir> {
+        let left = self.held.holding(&self.bindings, Action::MoveLeft);
+        let right = self.held.holding(&self.bindings, Action::MoveRight);
+        match (left, right) {
+            (true, false) => Some(Dir::W),
+            (false, true) => Some(Dir::E),
+            _ => None
+        }
+    }
+
+    // Shots covering several rows move a row at a time, with collisions
+    // checked after every row so nothing is jumped over
     fn move_shots(&mut self) {
-        for shot in self.shots.iter_mut() {
-            if shot.active {
-                let new_pos = shot.next_pos();
-                if new_pos.is_legal() {
-                    shot.pos = new_pos;
-                } else {
-                    shot.deactivate();
-                    if shot.player_fired {
-                        self.player.active_shots -= 1;
-                    } else {
-                        self.aliens.active_shots -= 1;
-                    }
-                }
-            }
+        self.player_shots.start_update();
+        self.alien_shots.start_update();
+        for _ in 0..MAX_ROWS_PER_UPDATE {
+            self.player_shots.step::<WIDTH, HEIGHT>();
+            self.alien_shots.step::<WIDTH, HEIGHT>();
+            self.place_shots();
+            self.check_collisions();
+            self.place_shots();
         }
     }
 
     fn check_collisions(&mut self) {
-        for i in 0..self.shots.len() {
-            if self.shots.get(i).unwrap().active {
-                if self.check_shot_collision(i) {
-                    self.shots.get_mut(i).unwrap().deactivate();
-                    if self.shots.get(i).unwrap().fired_by_player() {
-                        self.player.active_shots -= 1;
-                    } else {
-                        self.aliens.active_shots -= 1;
-                    }
-                }
+        self.check_shots_meet();
+        for i in 0..self.player_shots.shots.len() {
+            let shot = self.player_shots.shots[i];
+            if shot.active && self.player_shot_hit(shot.pos) {
+                self.player_shots.shots[i].deactivate();
+            }
+        }
+        for i in 0..self.alien_shots.shots.len() {
+            let shot = self.alien_shots.shots[i];
+            if shot.active && self.alien_shot_hit(shot.pos) {
+                self.alien_shots.shots[i].deactivate();
             }
         }
     }
 
-    fn check_shot_collision(&mut self, shot_index: usize) -> bool {
-        let shot_pos = self.shots.get(shot_index).unwrap().pos;
-        let fired_by_player = self.shots.get(shot_index).unwrap().fired_by_player();
-        if self.cell(shot_pos) == Cell::Barrier {
-            let (row, col) = shot_pos.row_col();
-            self.cells[row][col] = Cell::Empty;
-            return true
-        } else if self.player_at(shot_pos) && !fired_by_player {
-            self.status = Status::Over;
-            return true
-        } else if fired_by_player {
-            match self.alien_at(shot_pos) {
-                Some((row, col, alien)) => {
-                    let alien = self.aliens.aliens.get_mut(row).unwrap().get_mut(col).unwrap();
-                    alien.alive = false;
-                    self.score += 1;
-                    return true
-                },
-                _ => {}
+    // A laser that meets a bomb takes it out, and is used up doing it
+    fn check_shots_meet(&mut self) {
+        for laser in self.player_shots.shots.iter_mut().filter(|shot| shot.active) {
+            if let Some(bomb) = self.alien_shots.shots.iter_mut().find(|bomb| bomb.active && laser.met(bomb)) {
+                bomb.deactivate();
+                laser.deactivate();
             }
         }
-        false
     }
 
-    fn find_usable_shot(&self) -> Option<usize> {
-        for i in 0..self.shots.len() {
-            if !self.shots.get(i).unwrap().active {
-                return Some(i)
+    // Walls and bunkers stop shots from either side; a bunker cell is worn
+    // down a stage by each hit
+    fn barrier_hit(&mut self, p: Position) -> bool {
+        let (row, col) = p.row_col();
+        let cell = self.cells[row][col];
+        self.cells[row][col] = cell.damaged();
+        cell.is_solid()
+    }
+
+    // Bombs also chip the bunker cells beside and below the one they hit
+    fn splash(&mut self, p: Position) {
+        for dir in [Dir::E, Dir::W, Dir::S] {
+            let neighbor = p.neighbor(dir);
+            if neighbor.is_legal::<WIDTH, HEIGHT>() {
+                let (row, col) = neighbor.row_col();
+                self.cells[row][col] = self.cells[row][col].damaged();
             }
         }
-        None
     }
 
-    fn find_shot_for_player(&self) -> Option<usize> {
-        if self.player.can_fire_shot() {
-            return self.find_usable_shot()
+    fn player_shot_hit(&mut self, shot_pos: Position) -> bool {
+        if self.barrier_hit(shot_pos) {
+            return true
+        }
+        match self.entity_at(shot_pos) {
+            Some(EntityRef::Alien(i, _)) => {
+                self.alien_hit(i);
+                true
+            }
+            Some(EntityRef::Ufo(_)) => {
+                self.ufo_hit();
+                true
+            }
+            _ => false
         }
-        return None
     }
 
-    fn player_shoot(&mut self) {
-        let fireable_shot = self.find_shot_for_player();
-        match fireable_shot {
-            Some(i) => {
-                let pos = self.player.get_shot_position();
-                let shot: &mut Shot = self.shots.get_mut(i).unwrap();
-                shot.fire(pos, Dir::N, true);
-                self.player.active_shots += 1;
-            },
-            None => {}
+    fn alien_hit(&mut self, i: usize) {
+        let alien = self.aliens.aliens.get_mut(i).unwrap();
+        alien.hits -= 1;
+        if alien.hits == 0 {
+            alien.alive = false;
+            let (pos, points) = (alien.pos, alien.kind.points());
+            self.occupancy.remove_alien(pos);
+            self.add_score(points);
+            self.play(Sound::AlienExplosion);
         }
     }
 
-    fn find_shot_for_aliens(&self) -> Option<usize> {
-        if self.aliens.can_fire_shot() {
-            return self.find_usable_shot()
+    fn ufo_hit(&mut self) {
+        let points = UFO_POINTS[self.player.shots_fired % UFO_POINTS.len()];
+        self.add_score(points);
+        self.ufo.active = false;
+        let col = (self.ufo.pos.col + UFO_WIDTH / 2) as usize;
+        self.ufo_popup = Some(UfoPopup {col, points, countdown: UFO_POPUP_TIME});
+        self.play(Sound::AlienExplosion);
+    }
+
+    fn alien_shot_hit(&mut self, shot_pos: Position) -> bool {
+        if let Cell::Barrier(_) = self.cell(shot_pos) {
+            self.splash(shot_pos);
+        }
+        if self.barrier_hit(shot_pos) {
+            return true
+        }
+        if let Some(EntityRef::Player(_)) = self.entity_at(shot_pos) {
+            if self.invincible {
+                return true
+            }
+            // Every shot, this one included, is already cleared away
+            self.player_hit();
+        }
+        false
+    }
+
+    fn player_shoot(&mut self) {
+        let pos = self.player.get_shot_position();
+        if self.player_shots.fire(pos, Dir::N, ShotKind::Laser) {
+            self.place_shots();
+            self.player.shots_fired += 1;
+            self.play(Sound::Shot);
         }
-        None
     }
 
     fn alien_shoot(&mut self) {
-        let fireable_shot = self.find_shot_for_aliens();
-        match fireable_shot {
-            Some(i) => {
-                let alien_to_shoot = self.aliens.find_alien_to_fire(&self.player);
-                match alien_to_shoot {
-                    Some(alien) => {
-                        let pos = alien.get_shot_pos();
-                        let shot: &mut Shot = self.shots.get_mut(i).unwrap();
-                        shot.fire(pos, Dir::S, false);
-                        self.aliens.active_shots += 1;
-                    },
-                    None => {}
-                }
-            }
-            None => {}
+        if !self.aliens.ready_to_fire() || !self.alien_shots.can_fire() {
+            return
+        }
+        let alien_to_shoot = self.aliens.find_alien_to_fire::<WIDTH>(&self.player, &mut self.rng);
+        if let Some(alien) = alien_to_shoot {
+            let pos = alien.get_shot_pos();
+            let kind = alien.kind.shot_kind();
+            self.aliens.fire_countdown = alien.kind.reload();
+            self.alien_shots.fire(pos, Dir::S, kind);
+            self.place_shots();
         }
     }
 
+    pub fn input(&mut self, input: KeyInput) {
+        match input {
+            KeyInput::Typed(key) => self.key(key),
+            KeyInput::Down(code) => self.held.press(code),
+            KeyInput::Up(code) => self.held.release(code)
+        }
+    }
+
+    // A key going down or up, as the keyboard reports it before decoding
+    pub fn key_event(&mut self, event: &KeyEvent) {
+        self.input(KeyInput::from(event));
+    }
+
+    // Any key stops the demo; on the title screen it puts the demo off
     pub fn key(&mut self, key: DecodedKey) {
+        if self.demo {
+            self.end_demo();
+            return
+        }
+        if self.status == Status::Title {
+            self.attract_countdown = TITLE_TIME;
+        }
+        let typing = matches!(self.status, Status::EnteringInitials | Status::Options);
+        if !typing && self.bindings.is(key, Action::Mute) {
+            self.muted = !self.muted;
+            self.sounds = Sounds::new();
+            return
+        }
+        let is = |action| self.bindings.is(key, action);
         match self.status {
+            Status::Title => {
+                if is(Action::Start) {
+                    self.start(1);
+                } else if is(Action::TwoPlayers) {
+                    self.start(2);
+                } else if is(Action::Options) {
+                    self.rebinding = None;
+                    self.status = Status::Options;
+                }
+            }
+            Status::Options => self.options_key(key),
+            Status::EnteringInitials => self.enter_initial(key),
             Status::Over => {
-                match key {
-                    DecodedKey::RawKey(KeyCode::S) | DecodedKey::Unicode('s') => self.reset(),
-                    _ => {}
+                if is(Action::Restart) {
+                    self.start(self.players);
+                }
+            }
+            Status::Paused => {
+                if is(Action::Pause) {
+                    self.resume();
+                }
+            }
+            Status::NextWave | Status::Dying | Status::NextPlayer => {
+                if is(Action::Pause) {
+                    self.pause();
                 }
             }
             Status::Normal => {
-                match key {
-                    DecodedKey::RawKey(KeyCode::Spacebar) | DecodedKey::Unicode(' ')=> {self.fired_shot = true},
-                    DecodedKey::RawKey(k) => match k {
-                    KeyCode::ArrowLeft => {
-                        self.last_dir = Some(Dir::W);
-                    },
-                    KeyCode::ArrowRight => {self.last_dir = Some(Dir::E)},
-                    _                 => {}
-                },
-                    _ => {}
+                if is(Action::Pause) {
+                    self.pause();
+                } else if is(Action::MoveLeft) {
+                    self.last_dir = Some(Dir::W);
+                } else if is(Action::MoveRight) {
+                    self.last_dir = Some(Dir::E);
+                } else if is(Action::Fire) {
+                    self.fired_shot = true;
                 }
             }
         }
     }
 
+    // One timer tick: updates the game when its countdown is up. True if the
+    // frame should be redrawn.
+    pub fn tick(&mut self) -> bool {
+        if self.countdown_complete() {
+            self.update();
+            true
+        } else {
+            false
+        }
+    }
+
+    // Timers stay frozen while paused, but the frame is still redrawn
     pub fn countdown_complete(&mut self) -> bool {
+        if self.status == Status::Paused {
+            return true
+        }
         if self.countdown == 0 {
             self.countdown = UPDATE_FREQUENCY;
             true
@@ -567,23 +1512,11 @@ impl SpaceInvadersGame {
     }
 }
 
-fn check_valid_key(key: DecodedKey) -> Option<KeyCode> {
-    match key {
-        DecodedKey::RawKey(k) => match k {
-            KeyCode::ArrowLeft => Some(k),
-            KeyCode::ArrowRight => Some(k),
-            KeyCode::Spacebar => Some(k),
-            _ => None
-        },
-        _ => None
-    }
-}
-
-pub struct RowColIter {
+pub struct RowColIter<const WIDTH: usize, const HEIGHT: usize> {
     row: usize, col: usize
 }
 
-impl Iterator for RowColIter {
+impl<const WIDTH: usize, const HEIGHT: usize> Iterator for RowColIter<WIDTH, HEIGHT> {
     type Item = Position;
 
     fn next(&mut self) -> Option<Self::Item> {
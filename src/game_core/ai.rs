@@ -0,0 +1,122 @@
+// A simple player for the attract-mode demo, written against the game's
+// public API only: step out from under incoming bombs, line up under the
+// lowest alien and fire once it is overhead. Host tests use it as a bot that
+// plays long games looking for panics.
+
+use super::{SpaceInvadersGame, Status, Dir, Position};
+use super::input::Action;
+use super::shots::ShotKind;
+
+const DANGER_ROWS: usize = 6; // bombs this close above the player are dodged
+
+// The action to take this update, if any
+pub fn choose<const WIDTH: usize, const HEIGHT: usize>(game: &SpaceInvadersGame<WIDTH, HEIGHT>) -> Option<Action> {
+    if game.status() != Status::Normal {
+        return None
+    }
+    let pos = game.player().pos();
+    let (_, col) = pos.row_col();
+    if in_danger(game, col) {
+        return [Dir::W, Dir::E].iter()
+            .find(|dir| matches!(can_step(game, pos, **dir), Some(next) if !in_danger(game, next)))
+            .map(|dir| move_action(*dir))
+    }
+    let target = target_column(game)?;
+    if target == col {
+        Some(Action::Fire)
+    } else {
+        let dir = if target < col {Dir::W} else {Dir::E};
+        match can_step(game, pos, dir) {
+            Some(next) if !in_danger(game, next) => Some(move_action(dir)),
+            _ => None
+        }
+    }
+}
+
+// Presses the key bound to the chosen action, for driving a game from outside
+pub fn play<const WIDTH: usize, const HEIGHT: usize>(game: &mut SpaceInvadersGame<WIDTH, HEIGHT>) {
+    if let Some(key) = choose(game).and_then(|action| game.bindings().keys(action).next()) {
+        game.key(key);
+    }
+}
+
+fn move_action(dir: Dir) -> Action {
+    if dir == Dir::W {Action::MoveLeft} else {Action::MoveRight}
+}
+
+// The column the player would reach, if nothing is in the way
+fn can_step<const WIDTH: usize, const HEIGHT: usize>(game: &SpaceInvadersGame<WIDTH, HEIGHT>, pos: Position, dir: Dir) -> Option<usize> {
+    let next = pos.neighbor(dir);
+    if next.is_legal::<WIDTH, HEIGHT>() && !game.cell(next).is_solid() {
+        Some(next.row_col().1)
+    } else {
+        None
+    }
+}
+
+// True if a bomb close above could land in the column; zig-zag bombs can
+// drift a column either way
+fn in_danger<const WIDTH: usize, const HEIGHT: usize>(game: &SpaceInvadersGame<WIDTH, HEIGHT>, col: usize) -> bool {
+    let (player_row, _) = game.player().pos().row_col();
+    game.shots().filter(|shot| shot.kind() != ShotKind::Laser).any(|shot| {
+        let (row, shot_col) = shot.pos().row_col();
+        let reach = if shot.kind() == ShotKind::ZigZag {1} else {0};
+        row <= player_row && player_row - row <= DANGER_ROWS && col.max(shot_col) - col.min(shot_col) <= reach
+    })
+}
+
+// The lowest live alien's column, the nearest to the player on a tie
+fn target_column<const WIDTH: usize, const HEIGHT: usize>(game: &SpaceInvadersGame<WIDTH, HEIGHT>) -> Option<usize> {
+    let (_, col) = game.player().pos().row_col();
+    game.live_aliens()
+        .map(|alien| alien.pos().row_col())
+        .max_by_key(|(row, alien_col)| (*row, usize::MAX - (col.max(*alien_col) - col.min(*alien_col))))
+        .map(|(_, alien_col)| alien_col)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_core::sim::*;
+
+    #[test]
+    fn dodges_a_bomb_overhead() {
+        let mut sim = Sim::blank();
+        let (row, col) = sim.player_pos();
+        sim.place_alien(row - 4, col).hold_formation().hold_alien_fire();
+        sim.game.alien_shots.set_capacity(1);
+        sim.game.alien_shots.fire(pos(row - 3, col), Dir::S, ShotKind::Straight);
+        assert!(matches!(choose(&sim.game), Some(Action::MoveLeft) | Some(Action::MoveRight)));
+    }
+
+    #[test]
+    fn lines_up_under_the_lowest_alien_and_fires() {
+        let mut sim = Sim::blank();
+        let (_, col) = sim.player_pos();
+        sim.place_alien(3, col).place_alien(6, col + 2).hold_formation().hold_alien_fire();
+        assert_eq!(choose(&sim.game), Some(Action::MoveRight));
+        sim.play(&[RIGHT, Input::Updates(1), RIGHT, Input::Updates(1)]);
+        assert_eq!(choose(&sim.game), Some(Action::Fire));
+    }
+
+    // Long unattended games, restarted whenever they end, to shake out
+    // panics in rarely reached states
+    #[test]
+    fn bot_plays_long_games_without_panicking() {
+        for seed in 1..=4 {
+            let mut sim = Sim::new();
+            sim.game.seed(seed);
+            let mut best = 0;
+            for _ in 0..5000 {
+                match sim.game.status() {
+                    Status::EnteringInitials => {sim.type_text("BOT\n");}
+                    Status::Over => {sim.play(&[RESTART]);}
+                    _ => play(&mut sim.game)
+                }
+                sim.run(1);
+                best = best.max(sim.game.score());
+            }
+            assert!(best > 0, "seed {} never scored", seed);
+        }
+    }
+}
@@ -0,0 +1,243 @@
+// A line-oriented debug console. The kernel feeds it the bytes arriving on
+// COM1 and gives it the port to answer on, so a running game can be
+// inspected and nudged under `qemu -serial stdio` without touching the VGA
+// view. Type "help" for the commands.
+
+use core::fmt::{self, Write};
+use super::{SpaceInvadersGame, Position, Status};
+use super::shots::ShotKind;
+
+pub const LINE_SIZE: usize = 64;
+const PROMPT: &str = "> ";
+const BACKSPACE: u8 = 0x08;
+const DELETE: u8 = 0x7F;
+
+const HELP: &str = "\
+status          show the game's state
+score [N]       show or set the score
+wave N          start wave N, keeping score and lives
+aliens          draw the alien formation
+shots           list the shots in flight
+invincible      toggle whether alien shots can hit the player
+frametime       toggle the cycles-per-frame counter on screen
+ufo             send the mystery ship across
+step [N]        run N updates (default 1) while paused
+speed [N]       show or set the updates between formation steps
+";
+
+#[derive(Copy,Clone,Eq,PartialEq,Debug)]
+pub struct Console {
+    line: [u8; LINE_SIZE],
+    len: usize
+}
+
+impl Default for Console {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Console {
+    pub const fn new() -> Self {
+        Console {line: [0; LINE_SIZE], len: 0}
+    }
+
+    // Echoes the byte back and runs the line once it is complete. Characters
+    // past the end of a full line are dropped.
+    pub fn input<W: Write + ?Sized, const WIDTH: usize, const HEIGHT: usize>(&mut self, byte: u8, game: &mut SpaceInvadersGame<WIDTH, HEIGHT>, out: &mut W) -> fmt::Result {
+        match byte {
+            b'\r' | b'\n' => {
+                let line = core::str::from_utf8(&self.line[..self.len]).unwrap_or("");
+                if !line.trim().is_empty() {
+                    out.write_str("\r\n")?;
+                    run(line, game, out)?;
+                    out.write_str(PROMPT)?;
+                }
+                self.len = 0;
+            }
+            BACKSPACE | DELETE if self.len > 0 => {
+                self.len -= 1;
+                out.write_str("\x08 \x08")?;
+            }
+            b' '..=b'~' if self.len < LINE_SIZE => {
+                self.line[self.len] = byte;
+                self.len += 1;
+                out.write_char(byte as char)?;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+// Runs one command line against the game
+pub fn run<W: Write + ?Sized, const WIDTH: usize, const HEIGHT: usize>(line: &str, game: &mut SpaceInvadersGame<WIDTH, HEIGHT>, out: &mut W) -> fmt::Result {
+    let mut words = line.split_whitespace();
+    let command = words.next().unwrap_or("");
+    let arg = words.next();
+    let number = arg.map(|arg| arg.parse::<u64>());
+    match (command, number) {
+        (_, Some(Err(_))) => writeln!(out, "expected a number, not '{}'\r", arg.unwrap_or("")),
+        ("help", _) => {
+            for help_line in HELP.lines() {
+                writeln!(out, "{}\r", help_line)?;
+            }
+            Ok(())
+        }
+        ("status", None) => write_status(game, out),
+        ("score", None) => writeln!(out, "score {}\r", game.score()),
+        ("score", Some(Ok(score))) => {
+            game.set_score(score);
+            writeln!(out, "score {}\r", game.score())
+        }
+        ("wave", Some(Ok(wave))) if wave > 0 => {
+            game.set_wave(wave as usize);
+            writeln!(out, "wave {}\r", game.wave())
+        }
+        ("aliens", None) => write_formation(game, out),
+        ("shots", None) => write_shots(game, out),
+        ("invincible", None) => {
+            game.set_invincible(!game.invincible());
+            writeln!(out, "invincible {}\r", on_off(game.invincible()))
+        }
+        ("frametime", None) => {
+            game.set_frame_time(!game.frame_time());
+            writeln!(out, "frametime {}\r", on_off(game.frame_time()))
+        }
+        ("ufo", None) => {
+            game.spawn_ufo();
+            writeln!(out, "ufo launched\r")
+        }
+        ("step", steps) => {
+            if game.status() != Status::Paused {
+                return writeln!(out, "pause the game first\r")
+            }
+            let steps = match steps {
+                Some(Ok(steps)) => steps,
+                _ => 1
+            };
+            for _ in 0..steps {
+                game.step_paused();
+            }
+            write_status(game, out)
+        }
+        ("speed", None) => writeln!(out, "speed {}\r", game.alien_move_frequency()),
+        ("speed", Some(Ok(speed))) => {
+            game.set_alien_move_frequency(speed as usize);
+            writeln!(out, "speed {}\r", game.alien_move_frequency())
+        }
+        _ => writeln!(out, "unknown command '{}'; try help\r", line.trim())
+    }
+}
+
+fn on_off(flag: bool) -> &'static str {
+    if flag {"on"} else {"off"}
+}
+
+fn write_status<W: Write + ?Sized, const WIDTH: usize, const HEIGHT: usize>(game: &SpaceInvadersGame<WIDTH, HEIGHT>, out: &mut W) -> fmt::Result {
+    writeln!(out, "status {:?} wave {} lives {} score {} aliens {} shots {} speed {} invincible {}\r",
+             game.status(), game.wave(), game.lives(), game.score(), game.live_aliens().count(),
+             game.shots().count(), game.alien_move_frequency(), on_off(game.invincible()))
+}
+
+// The rows and columns the formation spans, with '.' where an alien is gone
+fn write_formation<W: Write + ?Sized, const WIDTH: usize, const HEIGHT: usize>(game: &SpaceInvadersGame<WIDTH, HEIGHT>, out: &mut W) -> fmt::Result {
+    let bounds = match game.aliens.bounds() {
+        Some(bounds) => bounds,
+        None => return writeln!(out, "no aliens\r")
+    };
+    let (top, left) = bounds.0.row_col();
+    let (bottom, right) = bounds.1.row_col();
+    writeln!(out, "rows {}-{}, columns {}-{}\r", top, bottom, left, right)?;
+    for row in top..=bottom {
+        write!(out, "{:2} ", row)?;
+        for col in left..=right {
+            let icon = match game.alien_at(Position {row: row as i16, col: col as i16}) {
+                Some((_, alien)) => alien.icon(),
+                None => '.'
+            };
+            out.write_char(icon)?;
+        }
+        out.write_str("\r\n")?;
+    }
+    Ok(())
+}
+
+fn write_shots<W: Write + ?Sized, const WIDTH: usize, const HEIGHT: usize>(game: &SpaceInvadersGame<WIDTH, HEIGHT>, out: &mut W) -> fmt::Result {
+    let mut any = false;
+    for shot in game.shots() {
+        let (row, col) = shot.pos().row_col();
+        let side = if shot.kind() == ShotKind::Laser {"player"} else {"alien"};
+        writeln!(out, "{} {:?} at row {}, column {}\r", side, shot.kind(), row, col)?;
+        any = true;
+    }
+    if !any {
+        writeln!(out, "no shots\r")?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_core::sim::*;
+
+    fn run_command(sim: &mut Sim, line: &str) -> String {
+        let mut out = String::new();
+        run(line, &mut sim.game, &mut out).unwrap();
+        out
+    }
+
+    #[test]
+    fn commands_change_the_game() {
+        let mut sim = Sim::new();
+        assert_eq!(run_command(&mut sim, "score 1234"), "score 1234\r\n");
+        assert_eq!(sim.game.score(), 1234);
+        run_command(&mut sim, "wave 3");
+        assert_eq!(sim.game.wave(), 3);
+        run_command(&mut sim, "speed 9");
+        assert_eq!(sim.game.alien_move_frequency(), 9);
+        assert_eq!(run_command(&mut sim, "invincible"), "invincible on\r\n");
+        assert!(sim.game.invincible());
+        assert_eq!(run_command(&mut sim, "frametime"), "frametime on\r\n");
+        assert!(sim.game.frame_time());
+        run_command(&mut sim, "ufo");
+        assert!(sim.game.ufo().active);
+        assert!(run_command(&mut sim, "score lots").starts_with("expected a number"));
+        assert_eq!(run_command(&mut sim, "score 18446744073709551615"), "score 18446744073709551615\r\n");
+        let lives = sim.game.lives();
+        sim.game.add_score(100);
+        assert_eq!((sim.game.score(), sim.game.lives()), (u64::MAX, lives));
+        assert!(run_command(&mut sim, "warp 9").starts_with("unknown command"));
+    }
+
+    #[test]
+    fn step_runs_single_updates_while_paused() {
+        let mut sim = Sim::new();
+        assert_eq!(run_command(&mut sim, "step"), "pause the game first\r\n");
+        sim.play(&[PAUSE]);
+        let before = sim.game.ufo().countdown;
+        run_command(&mut sim, "step 3");
+        assert_eq!(sim.game.status(), Status::Paused);
+        assert_eq!(sim.game.ufo().countdown, before - 3);
+    }
+
+    #[test]
+    fn formation_is_drawn_row_by_row() {
+        let mut sim = Sim::blank();
+        sim.place_alien(2, 5).place_alien(3, 7);
+        assert_eq!(run_command(&mut sim, "aliens"), "rows 2-3, columns 5-7\r\n 2 @..\r\n 3 ..@\r\n");
+    }
+
+    #[test]
+    fn lines_are_echoed_and_run_on_enter() {
+        let mut sim = Sim::new();
+        let mut console = Console::new();
+        let mut out = String::new();
+        for byte in b"scorx\x7fe 5\r" {
+            console.input(*byte, &mut sim.game, &mut out).unwrap();
+        }
+        assert_eq!(out, "scorx\x08 \x08e 5\r\nscore 5\r\n> ");
+        assert_eq!(sim.game.score(), 5);
+    }
+}
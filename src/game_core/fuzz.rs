@@ -0,0 +1,230 @@
+// Property testing for the game's invariants. Random streams of keys and
+// timer ticks are played into a game, with the invariants checked after
+// every step. A failing stream is shrunk to the fewest steps that still fail
+// and reported as a replay log, so it can be pasted into a test with
+// Sim::replay or fed back to the kernel.
+//
+// The shots in flight used to be counted separately from the shots
+// themselves; the pools now count their own, so the shot checks here are
+// that the pools stay within capacity and that the occupancy grid agrees with
+// them.
+//
+// FUZZ_CASES in the environment runs more cases than the default.
+
+use std::panic::{self, AssertUnwindSafe};
+use pc_keyboard::{DecodedKey, KeyCode};
+use super::{Position, Status, MAX_LIVES};
+use super::sim::Game;
+use super::level::{Level, LEVELS};
+use super::{BOARD_WIDTH as WIDTH, BOARD_HEIGHT as HEIGHT};
+use super::high_scores::HighScores;
+use super::input::KeyInput;
+use super::replay::Recording;
+use super::rng::Rng;
+
+const DEFAULT_CASES: usize = 48;
+const CASE_STEPS: usize = 1500;
+const KEY_CHANCE: usize = 4; // one step in this many is a key rather than a tick
+
+// Everything a player could reasonably type, including the keys no control uses
+const TYPED: [DecodedKey; 16] = [
+    DecodedKey::RawKey(KeyCode::ArrowLeft), DecodedKey::RawKey(KeyCode::ArrowRight),
+    DecodedKey::Unicode(' '), DecodedKey::Unicode('\n'), DecodedKey::Unicode('p'),
+    DecodedKey::Unicode('s'), DecodedKey::Unicode('m'), DecodedKey::Unicode('o'),
+    DecodedKey::Unicode('2'), DecodedKey::Unicode('a'), DecodedKey::Unicode('d'),
+    DecodedKey::Unicode('w'), DecodedKey::Unicode('5'), DecodedKey::Unicode('\u{1b}'),
+    DecodedKey::Unicode('\u{8}'), DecodedKey::Unicode('x')
+];
+const HELD: [KeyCode; 5] = [KeyCode::ArrowLeft, KeyCode::ArrowRight, KeyCode::A, KeyCode::D, KeyCode::Spacebar];
+
+#[derive(Copy,Clone,Eq,PartialEq,Debug)]
+enum Step {
+    Input(KeyInput),
+    Tick
+}
+
+type Property = fn(&Game) -> Result<(), String>;
+
+#[derive(Clone,Debug)]
+struct Case {
+    seed: u64,
+    steps: Vec<Step>
+}
+
+impl Case {
+    // Starts a game, then mostly ticks with keys scattered among them
+    fn generate(rng: &mut Rng, len: usize) -> Self {
+        let mut steps = vec![Step::Input(KeyInput::Typed(DecodedKey::Unicode('\n')))];
+        while steps.len() < len {
+            let step = if rng.below(KEY_CHANCE) != 0 {
+                Step::Tick
+            } else {
+                match rng.below(3) {
+                    0 => Step::Input(KeyInput::Typed(TYPED[rng.below(TYPED.len())])),
+                    1 => Step::Input(KeyInput::Down(HELD[rng.below(HELD.len())])),
+                    _ => Step::Input(KeyInput::Up(HELD[rng.below(HELD.len())]))
+                }
+            };
+            steps.push(step);
+        }
+        Case {seed: rng.next_u64(), steps}
+    }
+
+    fn recording(&self) -> Recording {
+        let mut recording = Recording::new(self.seed, HighScores::new());
+        for step in self.steps.iter() {
+            match step {
+                Step::Input(input) => recording.record_input(*input),
+                Step::Tick => recording.tick()
+            }
+        }
+        recording
+    }
+
+    fn log(&self) -> String {
+        let mut log = String::new();
+        self.recording().write_to(&mut log).unwrap();
+        log
+    }
+
+    // The step the property first fails after, and why. A panic inside the
+    // game counts as a failure.
+    fn failure(&self, property: Property) -> Option<(usize, String)> {
+        let mut game = self.recording().start_game();
+        for (i, step) in self.steps.iter().enumerate() {
+            let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                match step {
+                    Step::Input(input) => game.input(*input),
+                    Step::Tick => {game.tick();}
+                }
+            }));
+            let checked = match result {
+                Ok(()) => property(&game),
+                Err(cause) => Err(panic_message(&*cause))
+            };
+            if let Err(reason) = checked {
+                return Some((i, reason))
+            }
+        }
+        None
+    }
+
+    // Drops everything after the failing step, then repeatedly removes runs
+    // of steps, halving the run length whenever nothing more can go, for as
+    // long as the property still fails
+    fn shrink(&self, property: Property) -> Case {
+        let mut case = self.clone();
+        let mut end = match case.failure(property) {
+            Some((i, _)) => i + 1,
+            None => return case
+        };
+        case.steps.truncate(end);
+        let mut chunk = (case.steps.len() / 2).max(1);
+        loop {
+            let mut start = 0;
+            while start < case.steps.len() {
+                let mut smaller = case.clone();
+                smaller.steps.drain(start..(start + chunk).min(case.steps.len()));
+                match smaller.failure(property) {
+                    Some((i, _)) => {
+                        end = i + 1;
+                        smaller.steps.truncate(end);
+                        case = smaller;
+                    }
+                    None => start += chunk
+                }
+            }
+            if chunk == 1 {
+                return case
+            }
+            chunk /= 2;
+        }
+    }
+}
+
+fn panic_message(cause: &(dyn std::any::Any + Send)) -> String {
+    match (cause.downcast_ref::<&str>(), cause.downcast_ref::<String>()) {
+        (Some(message), _) => format!("panicked: {}", message),
+        (_, Some(message)) => format!("panicked: {}", message),
+        _ => "panicked".to_string()
+    }
+}
+
+// The invariants every reachable state keeps
+fn invariants(game: &Game) -> Result<(), String> {
+    for (name, pool) in [("player", &game.player_shots), ("alien", &game.alien_shots)].iter() {
+        if pool.active_count() > pool.capacity() {
+            return Err(format!("{} shots in flight: {} of {}", name, pool.active_count(), pool.capacity()))
+        }
+        for (i, shot) in pool.shots.iter().enumerate().filter(|(_, shot)| shot.active) {
+            if !game.is_legal(shot.pos) {
+                return Err(format!("{} shot off the board at {:?}", name, shot.pos))
+            }
+            if game.occupancy.shot_at(shot.pos).is_none() {
+                return Err(format!("{} shot {} missing from the grid at {:?}", name, i, shot.pos))
+            }
+        }
+    }
+    for (i, alien) in game.aliens.aliens.iter().enumerate().filter(|(_, alien)| alien.alive) {
+        if !game.is_legal(alien.pos) || game.occupancy.alien_at(alien.pos) != Some(i) {
+            return Err(format!("alien {} not in the grid at {:?}", i, alien.pos))
+        }
+    }
+    let player = game.player.pos;
+    if !game.is_legal(player) || game.cell(player).is_solid() {
+        return Err(format!("player inside a wall or bunker at {:?}", player))
+    }
+    if game.lives > MAX_LIVES {
+        return Err(format!("{} lives", game.lives))
+    }
+    Ok(())
+}
+
+// Runs the cases, panicking with the shrunk log of the first failure
+fn check(property: Property, cases: usize, steps: usize) {
+    let mut rng = Rng::default();
+    for n in 0..cases {
+        let case = Case::generate(&mut rng, steps);
+        if case.failure(property).is_some() {
+            let shrunk = case.shrink(property);
+            let (step, reason) = shrunk.failure(property).unwrap();
+            panic!("case {} fails after step {}: {}\nreplay log:\n{}", n, step, reason, shrunk.log());
+        }
+    }
+}
+
+#[test]
+fn random_play_keeps_the_invariants() {
+    let cases = std::env::var("FUZZ_CASES").ok().and_then(|cases| cases.parse().ok()).unwrap_or(DEFAULT_CASES);
+    check(invariants, cases, CASE_STEPS);
+}
+
+#[test]
+fn failures_shrink_to_a_short_replayable_log() {
+    // Fails on any move left: starting the game, a left key and a tick
+    fn stays_put(game: &Game) -> Result<(), String> {
+        if game.player.pos.col < Level::<WIDTH, HEIGHT>::for_wave(&LEVELS, 1).player.col {Err("moved left".to_string())} else {Ok(())}
+    }
+    let case = Case::generate(&mut Rng::new(7), CASE_STEPS);
+    assert!(case.failure(stays_put).is_some(), "random play never moved left");
+    let shrunk = case.shrink(stays_put);
+    assert_eq!(shrunk.steps.len(), 3, "{:?}", shrunk.steps);
+    let start = Level::<WIDTH, HEIGHT>::for_wave(&LEVELS, 1).player;
+    let replayed = super::sim::Sim::replay(&shrunk.log());
+    assert_eq!(replayed.game.player.pos, Position {row: start.row, col: start.col - 1});
+}
+
+#[test]
+fn failures_on_a_key_replay_up_to_that_key() {
+    // Fails as soon as the game is paused: starting it and a pause key, with
+    // no tick after the last step
+    fn never_paused(game: &Game) -> Result<(), String> {
+        if game.status() == Status::Paused {Err("paused".to_string())} else {Ok(())}
+    }
+    let case = Case::generate(&mut Rng::new(7), CASE_STEPS);
+    let shrunk = case.shrink(never_paused);
+    assert_eq!(shrunk.steps.len(), 2, "{:?}", shrunk.steps);
+    assert!(matches!(shrunk.steps[1], Step::Input(_)));
+    let replayed = super::sim::Sim::replay(&shrunk.log());
+    assert_eq!(replayed.game.status(), Status::Paused);
+}
@@ -0,0 +1,206 @@
+// The top ten scores with the initials of whoever set them. The table travels
+// to storage as a fixed-size record:
+//
+//   magic "SIHS", entry count, then per entry 3 initials and a little-endian
+//   u64 score, zero padding, and a final checksum byte
+//
+// so a blank or foreign disk reads back as an empty table rather than garbage.
+
+pub const MAX_HIGH_SCORES: usize = 10;
+pub const INITIALS_LEN: usize = 3;
+pub const RECORD_SIZE: usize = 128;
+
+const MAGIC: &[u8; 4] = b"SIHS";
+const ENTRY_SIZE: usize = INITIALS_LEN + 8;
+const ENTRIES_START: usize = MAGIC.len() + 1;
+
+// Where the table is kept between boots
+pub trait ScoreStorage {
+    fn load(&mut self) -> Option<[u8; RECORD_SIZE]>;
+    fn save(&mut self, record: &[u8; RECORD_SIZE]);
+}
+
+// Storage for host tests, and for running without a disk
+#[derive(Copy,Clone,Eq,PartialEq,Debug,Default)]
+pub struct MemoryStorage {
+    record: Option<[u8; RECORD_SIZE]>
+}
+
+impl ScoreStorage for MemoryStorage {
+    fn load(&mut self) -> Option<[u8; RECORD_SIZE]> {
+        self.record
+    }
+
+    fn save(&mut self, record: &[u8; RECORD_SIZE]) {
+        self.record = Some(*record);
+    }
+}
+
+#[derive(Copy,Clone,Eq,PartialEq,Debug)]
+pub struct HighScore {
+    initials: [u8; INITIALS_LEN],
+    score: u64
+}
+
+impl HighScore {
+    pub fn initials(&self) -> &str {
+        core::str::from_utf8(&self.initials).unwrap_or("???")
+    }
+
+    pub fn score(&self) -> u64 {
+        self.score
+    }
+}
+
+// Highest score first; a tie goes to whoever got there first
+#[derive(Copy,Clone,Eq,PartialEq,Debug)]
+pub struct HighScores {
+    entries: [HighScore; MAX_HIGH_SCORES],
+    len: usize
+}
+
+impl Default for HighScores {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HighScores {
+    pub fn new() -> Self {
+        HighScores {entries: [HighScore {initials: [b' '; INITIALS_LEN], score: 0}; MAX_HIGH_SCORES], len: 0}
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item=&HighScore> {
+        self.entries[..self.len].iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    // The place a score would take in the table, if it makes it in at all
+    pub fn rank(&self, score: u64) -> Option<usize> {
+        if score == 0 {
+            return None
+        }
+        let rank = self.iter().position(|entry| entry.score < score).unwrap_or(self.len);
+        if rank < MAX_HIGH_SCORES {Some(rank)} else {None}
+    }
+
+    pub fn insert(&mut self, initials: [u8; INITIALS_LEN], score: u64) -> Option<usize> {
+        let rank = self.rank(score)?;
+        self.len = (self.len + 1).min(MAX_HIGH_SCORES);
+        for i in (rank + 1..self.len).rev() {
+            self.entries[i] = self.entries[i - 1];
+        }
+        self.entries[rank] = HighScore {initials, score};
+        Some(rank)
+    }
+
+    pub fn load_from<S: ScoreStorage>(storage: &mut S) -> HighScores {
+        storage.load().and_then(|record| HighScores::from_record(&record)).unwrap_or_default()
+    }
+
+    pub fn save_to<S: ScoreStorage>(&self, storage: &mut S) {
+        storage.save(&self.record());
+    }
+
+    fn record(&self) -> [u8; RECORD_SIZE] {
+        let mut record = [0; RECORD_SIZE];
+        record[..MAGIC.len()].copy_from_slice(MAGIC);
+        record[MAGIC.len()] = self.len as u8;
+        for (i, entry) in self.iter().enumerate() {
+            let start = ENTRIES_START + i * ENTRY_SIZE;
+            record[start..start + INITIALS_LEN].copy_from_slice(&entry.initials);
+            record[start + INITIALS_LEN..start + ENTRY_SIZE].copy_from_slice(&entry.score.to_le_bytes());
+        }
+        record[RECORD_SIZE - 1] = checksum(&record);
+        record
+    }
+
+    fn from_record(record: &[u8; RECORD_SIZE]) -> Option<HighScores> {
+        if &record[..MAGIC.len()] != MAGIC || record[RECORD_SIZE - 1] != checksum(record) {
+            return None
+        }
+        let len = record[MAGIC.len()] as usize;
+        if len > MAX_HIGH_SCORES {
+            return None
+        }
+        let mut scores = HighScores::new();
+        for i in 0..len {
+            let start = ENTRIES_START + i * ENTRY_SIZE;
+            let mut initials = [0; INITIALS_LEN];
+            initials.copy_from_slice(&record[start..start + INITIALS_LEN]);
+            let mut score = [0; 8];
+            score.copy_from_slice(&record[start + INITIALS_LEN..start + ENTRY_SIZE]);
+            scores.entries[i] = HighScore {initials, score: u64::from_le_bytes(score)};
+        }
+        scores.len = len;
+        Some(scores)
+    }
+}
+
+fn checksum(record: &[u8; RECORD_SIZE]) -> u8 {
+    record[..RECORD_SIZE - 1].iter().fold(0u8, |sum, b| sum.wrapping_add(*b)).wrapping_neg()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(scores: &[u64]) -> HighScores {
+        let mut table = HighScores::new();
+        for score in scores {
+            table.insert(*b"AAA", *score);
+        }
+        table
+    }
+
+    fn scores_of(table: &HighScores) -> Vec<u64> {
+        table.iter().map(|entry| entry.score()).collect()
+    }
+
+    #[test]
+    fn keeps_the_best_ten_in_order() {
+        let mut table = table(&[50, 300, 10, 200, 90, 70, 20, 80, 40, 60, 30]);
+        assert_eq!(scores_of(&table), vec![300, 200, 90, 80, 70, 60, 50, 40, 30, 20]);
+        assert_eq!(table.rank(20), None);
+        assert_eq!(table.rank(0), None);
+        assert_eq!(table.insert(*b"BOB", 85), Some(3));
+        assert_eq!(scores_of(&table), vec![300, 200, 90, 85, 80, 70, 60, 50, 40, 30]);
+        assert_eq!(table.iter().nth(3).unwrap().initials(), "BOB");
+    }
+
+    #[test]
+    fn ties_go_to_the_earlier_score() {
+        let mut table = table(&[100]);
+        assert_eq!(table.insert(*b"NEW", 100), Some(1));
+        assert_eq!(table.iter().next().unwrap().initials(), "AAA");
+    }
+
+    #[test]
+    fn survives_a_round_trip_through_storage() {
+        let mut storage = MemoryStorage::default();
+        assert!(HighScores::load_from(&mut storage).is_empty());
+        let mut table = table(&[u64::MAX, 1234]);
+        table.insert(*b"XYZ", 500);
+        table.save_to(&mut storage);
+        assert_eq!(HighScores::load_from(&mut storage), table);
+    }
+
+    #[test]
+    fn corrupt_records_load_as_empty() {
+        let mut storage = MemoryStorage::default();
+        table(&[1234]).save_to(&mut storage);
+        let mut record = storage.load().unwrap();
+        record[ENTRIES_START + INITIALS_LEN] ^= 1;
+        storage.save(&record);
+        assert!(HighScores::load_from(&mut storage).is_empty());
+        storage.save(&[0; RECORD_SIZE]);
+        assert!(HighScores::load_from(&mut storage).is_empty());
+    }
+}
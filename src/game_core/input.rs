@@ -0,0 +1,323 @@
+// Turns keys into the actions the game understands. Each action has a few
+// keys bound to it, starting from one of the presets and changeable from the
+// options screen.
+//
+// Keys are compared after folding the different ways the keyboard can report
+// the same key together: letters ignore case and match their raw key code,
+// and Space, Enter, Escape, Backspace and the numpad digits match their
+// characters.
+
+use core::fmt;
+use pc_keyboard::{DecodedKey, KeyCode, KeyEvent, KeyState};
+
+pub const KEYS_PER_ACTION: usize = 3;
+pub const MAX_HELD_KEYS: usize = 6;
+const ENTER: DecodedKey = DecodedKey::Unicode('\n');
+const ESCAPE: DecodedKey = DecodedKey::Unicode('\u{1b}');
+const BACKSPACE: DecodedKey = DecodedKey::Unicode('\u{8}');
+const SPACE: DecodedKey = DecodedKey::Unicode(' ');
+
+#[derive(Copy,Clone,Eq,PartialEq,Debug)]
+pub enum Action {
+    MoveLeft,
+    MoveRight,
+    Fire,
+    Pause,
+    Start,
+    Confirm,
+    Restart,
+    Mute,
+    TwoPlayers,
+    Options
+}
+
+pub const ACTIONS: [Action; 10] = [Action::MoveLeft, Action::MoveRight, Action::Fire, Action::Pause,
+                                   Action::Start, Action::Confirm, Action::Restart, Action::Mute,
+                                   Action::TwoPlayers, Action::Options];
+
+impl Action {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::MoveLeft => "Move left",
+            Action::MoveRight => "Move right",
+            Action::Fire => "Fire",
+            Action::Pause => "Pause",
+            Action::Start => "Start",
+            Action::Confirm => "Confirm",
+            Action::Restart => "Restart",
+            Action::Mute => "Mute",
+            Action::TwoPlayers => "Two players",
+            Action::Options => "Options"
+        }
+    }
+
+    // Picks the action to rebind on the options screen: 1 to 9, then 0
+    pub fn menu_key(&self) -> char {
+        (b'0' + ((self.index() + 1) % 10) as u8) as char
+    }
+
+    fn index(&self) -> usize {
+        ACTIONS.iter().position(|action| action == self).unwrap()
+    }
+}
+
+// Everything the keyboard tells the game: keys as typed, with the
+// keyboard's own repeat, and keys going down and coming back up for the
+// controls that act for as long as a key is held
+#[derive(Copy,Clone,Eq,PartialEq,Debug)]
+pub enum KeyInput {
+    Typed(DecodedKey),
+    Down(KeyCode),
+    Up(KeyCode)
+}
+
+impl From<&KeyEvent> for KeyInput {
+    fn from(event: &KeyEvent) -> Self {
+        if event.state == KeyState::Down {
+            KeyInput::Down(event.code)
+        } else {
+            KeyInput::Up(event.code)
+        }
+    }
+}
+
+// The keys down right now. Only named keys are tracked, and only a few at
+// once, which is plenty for two hands on a keyboard.
+#[derive(Copy,Clone,Eq,PartialEq,Debug,Default)]
+pub struct HeldKeys {
+    keys: [Option<KeyCode>; MAX_HELD_KEYS]
+}
+
+impl HeldKeys {
+    pub fn press(&mut self, code: KeyCode) {
+        if key_name(code).is_none() || self.is_down(code) {
+            return
+        }
+        if let Some(slot) = self.keys.iter_mut().find(|slot| slot.is_none()) {
+            *slot = Some(code);
+        }
+    }
+
+    pub fn release(&mut self, code: KeyCode) {
+        for slot in self.keys.iter_mut().filter(|slot| **slot == Some(code)) {
+            *slot = None;
+        }
+    }
+
+    pub fn is_down(&self, code: KeyCode) -> bool {
+        self.keys.contains(&Some(code))
+    }
+
+    pub fn holding(&self, bindings: &KeyBindings, action: Action) -> bool {
+        self.keys.iter().flatten().any(|code| bindings.is(DecodedKey::RawKey(*code), action))
+    }
+}
+
+#[derive(Copy,Clone,Eq,PartialEq,Debug)]
+pub enum Preset {
+    Arrows,
+    Wasd,
+    Numpad
+}
+
+pub const PRESETS: [Preset; 3] = [Preset::Arrows, Preset::Wasd, Preset::Numpad];
+
+impl Preset {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Preset::Arrows => "Arrow keys",
+            Preset::Wasd => "WASD",
+            Preset::Numpad => "Numpad"
+        }
+    }
+
+    // Picks the preset on the options screen
+    pub fn menu_key(&self) -> char {
+        match self {
+            Preset::Arrows => 'a',
+            Preset::Wasd => 'w',
+            Preset::Numpad => 'n'
+        }
+    }
+}
+
+#[derive(Copy,Clone,Eq,PartialEq,Debug)]
+pub struct KeyBindings {
+    keys: [[Option<DecodedKey>; KEYS_PER_ACTION]; ACTIONS.len()]
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        KeyBindings::preset(Preset::Arrows)
+    }
+}
+
+impl KeyBindings {
+    pub fn preset(preset: Preset) -> Self {
+        let raw = DecodedKey::RawKey;
+        let char = DecodedKey::Unicode;
+        let (left, right, fire) = match preset {
+            Preset::Arrows => ([Some(raw(KeyCode::ArrowLeft)), None], [Some(raw(KeyCode::ArrowRight)), None], [Some(SPACE), None]),
+            Preset::Wasd => ([Some(char('a')), None], [Some(char('d')), None], [Some(char('w')), Some(SPACE)]),
+            Preset::Numpad => ([Some(char('4')), Some(raw(KeyCode::ArrowLeft))], [Some(char('6')), Some(raw(KeyCode::ArrowRight))],
+                               [Some(char('8')), Some(char('0'))])
+        };
+        let mut bindings = KeyBindings {keys: [[None; KEYS_PER_ACTION]; ACTIONS.len()]};
+        bindings.keys[Action::MoveLeft.index()][..2].copy_from_slice(&left);
+        bindings.keys[Action::MoveRight.index()][..2].copy_from_slice(&right);
+        bindings.keys[Action::Fire.index()][..2].copy_from_slice(&fire);
+        bindings.keys[Action::Pause.index()] = [Some(char('p')), Some(ESCAPE), None];
+        bindings.keys[Action::Start.index()] = [Some(ENTER), Some(SPACE), Some(char('s'))];
+        bindings.keys[Action::Confirm.index()] = [Some(ENTER), None, None];
+        bindings.keys[Action::Restart.index()] = [Some(char('s')), None, None];
+        bindings.keys[Action::Mute.index()] = [Some(char('m')), None, None];
+        bindings.keys[Action::TwoPlayers.index()] = [Some(char('2')), None, None];
+        bindings.keys[Action::Options.index()] = [Some(char('o')), None, None];
+        bindings
+    }
+
+    // The first action bound to the key, in ACTIONS order
+    pub fn action(&self, key: DecodedKey) -> Option<Action> {
+        let key = normalize(key);
+        ACTIONS.iter().copied().find(|action| self.keys(*action).any(|bound| bound == key))
+    }
+
+    pub fn is(&self, key: DecodedKey, action: Action) -> bool {
+        self.keys(action).any(|bound| bound == normalize(key))
+    }
+
+    pub fn keys(&self, action: Action) -> impl Iterator<Item=DecodedKey> + '_ {
+        self.keys[action.index()].iter().flatten().copied()
+    }
+
+    // Makes the key the only one for the action. Moving actions and fire
+    // can't share keys, so the key stops doing whichever of them it did.
+    pub fn bind(&mut self, action: Action, key: DecodedKey) {
+        let key = normalize(key);
+        let exclusive = [Action::MoveLeft, Action::MoveRight, Action::Fire];
+        if exclusive.contains(&action) {
+            for other in exclusive.iter() {
+                for slot in self.keys[other.index()].iter_mut() {
+                    if *slot == Some(key) {
+                        *slot = None;
+                    }
+                }
+            }
+        }
+        self.keys[action.index()] = [Some(key), None, None];
+    }
+
+    // Replaces the action's keys as they are, e.g. with ones read back from
+    // a recording
+    pub fn set_keys(&mut self, action: Action, keys: [Option<DecodedKey>; KEYS_PER_ACTION]) {
+        self.keys[action.index()] = keys;
+    }
+}
+
+pub fn normalize(key: DecodedKey) -> DecodedKey {
+    match key {
+        DecodedKey::Unicode(c) => DecodedKey::Unicode(c.to_ascii_lowercase()),
+        DecodedKey::RawKey(code) => match code {
+            KeyCode::Spacebar => SPACE,
+            KeyCode::Enter | KeyCode::NumpadEnter => ENTER,
+            KeyCode::Escape => ESCAPE,
+            KeyCode::Backspace => BACKSPACE,
+            _ => match key_name(code).map(str::as_bytes) {
+                Some([letter]) => DecodedKey::Unicode(letter.to_ascii_lowercase() as char),
+                Some([b'N', b'u', b'm', b'p', b'a', b'd', digit]) => DecodedKey::Unicode(*digit as char),
+                _ => key
+            }
+        }
+    }
+}
+
+// Raw keys with a name; others never reach the game's controls
+const KEY_NAMES: [(KeyCode, &str); 56] = [
+    (KeyCode::ArrowLeft, "ArrowLeft"), (KeyCode::ArrowRight, "ArrowRight"),
+    (KeyCode::ArrowUp, "ArrowUp"), (KeyCode::ArrowDown, "ArrowDown"),
+    (KeyCode::Spacebar, "Spacebar"), (KeyCode::Enter, "Enter"), (KeyCode::Escape, "Escape"),
+    (KeyCode::Backspace, "Backspace"), (KeyCode::Tab, "Tab"), (KeyCode::NumpadEnter, "NumpadEnter"),
+    (KeyCode::Numpad0, "Numpad0"), (KeyCode::Numpad1, "Numpad1"), (KeyCode::Numpad2, "Numpad2"),
+    (KeyCode::Numpad3, "Numpad3"), (KeyCode::Numpad4, "Numpad4"), (KeyCode::Numpad5, "Numpad5"),
+    (KeyCode::Numpad6, "Numpad6"), (KeyCode::Numpad7, "Numpad7"), (KeyCode::Numpad8, "Numpad8"),
+    (KeyCode::Numpad9, "Numpad9"),
+    (KeyCode::Key0, "Key0"), (KeyCode::Key1, "Key1"), (KeyCode::Key2, "Key2"), (KeyCode::Key3, "Key3"),
+    (KeyCode::Key4, "Key4"), (KeyCode::Key5, "Key5"), (KeyCode::Key6, "Key6"), (KeyCode::Key7, "Key7"),
+    (KeyCode::Key8, "Key8"), (KeyCode::Key9, "Key9"),
+    (KeyCode::A, "A"), (KeyCode::B, "B"), (KeyCode::C, "C"), (KeyCode::D, "D"), (KeyCode::E, "E"),
+    (KeyCode::F, "F"), (KeyCode::G, "G"), (KeyCode::H, "H"), (KeyCode::I, "I"), (KeyCode::J, "J"),
+    (KeyCode::K, "K"), (KeyCode::L, "L"), (KeyCode::M, "M"), (KeyCode::N, "N"), (KeyCode::O, "O"),
+    (KeyCode::P, "P"), (KeyCode::Q, "Q"), (KeyCode::R, "R"), (KeyCode::S, "S"), (KeyCode::T, "T"),
+    (KeyCode::U, "U"), (KeyCode::V, "V"), (KeyCode::W, "W"), (KeyCode::X, "X"), (KeyCode::Y, "Y"),
+    (KeyCode::Z, "Z")
+];
+
+pub fn key_name(code: KeyCode) -> Option<&'static str> {
+    KEY_NAMES.iter().find(|(known, _)| *known == code).map(|(_, name)| *name)
+}
+
+pub fn key_code(name: &str) -> Option<KeyCode> {
+    KEY_NAMES.iter().find(|(_, known)| *known == name).map(|(code, _)| *code)
+}
+
+// How a key is shown to the player
+pub struct KeyLabel(pub DecodedKey);
+
+impl fmt::Display for KeyLabel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match normalize(self.0) {
+            DecodedKey::Unicode(' ') => write!(f, "Space"),
+            DecodedKey::Unicode('\n') => write!(f, "Enter"),
+            DecodedKey::Unicode('\u{1b}') => write!(f, "Esc"),
+            DecodedKey::Unicode('\u{8}') => write!(f, "Backspace"),
+            DecodedKey::Unicode('\t') => write!(f, "Tab"),
+            DecodedKey::Unicode(c) => write!(f, "{}", c.to_ascii_uppercase()),
+            DecodedKey::RawKey(KeyCode::ArrowLeft) => write!(f, "Left"),
+            DecodedKey::RawKey(KeyCode::ArrowRight) => write!(f, "Right"),
+            DecodedKey::RawKey(KeyCode::ArrowUp) => write!(f, "Up"),
+            DecodedKey::RawKey(KeyCode::ArrowDown) => write!(f, "Down"),
+            DecodedKey::RawKey(code) => write!(f, "{}", key_name(code).unwrap_or("?"))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn presets_map_their_keys() {
+        let arrows = KeyBindings::preset(Preset::Arrows);
+        assert_eq!(arrows.action(DecodedKey::RawKey(KeyCode::ArrowLeft)), Some(Action::MoveLeft));
+        assert_eq!(arrows.action(DecodedKey::RawKey(KeyCode::Spacebar)), Some(Action::Fire));
+        assert_eq!(arrows.action(DecodedKey::Unicode('a')), None);
+        let wasd = KeyBindings::preset(Preset::Wasd);
+        assert_eq!(wasd.action(DecodedKey::Unicode('A')), Some(Action::MoveLeft));
+        assert_eq!(wasd.action(DecodedKey::RawKey(KeyCode::D)), Some(Action::MoveRight));
+        assert_eq!(wasd.action(DecodedKey::Unicode('w')), Some(Action::Fire));
+        let numpad = KeyBindings::preset(Preset::Numpad);
+        assert_eq!(numpad.action(DecodedKey::RawKey(KeyCode::Numpad4)), Some(Action::MoveLeft));
+        assert_eq!(numpad.action(DecodedKey::Unicode('6')), Some(Action::MoveRight));
+        assert_eq!(numpad.action(DecodedKey::RawKey(KeyCode::NumpadEnter)), Some(Action::Start));
+        assert!(numpad.is(DecodedKey::RawKey(KeyCode::Enter), Action::Confirm));
+    }
+
+    #[test]
+    fn rebinding_moves_a_key_between_actions() {
+        let mut bindings = KeyBindings::default();
+        bindings.bind(Action::Fire, DecodedKey::RawKey(KeyCode::ArrowLeft));
+        assert_eq!(bindings.action(DecodedKey::RawKey(KeyCode::ArrowLeft)), Some(Action::Fire));
+        assert_eq!(bindings.keys(Action::MoveLeft).count(), 0);
+        assert!(!bindings.is(DecodedKey::Unicode(' '), Action::Fire));
+        assert!(bindings.is(DecodedKey::Unicode(' '), Action::Start));
+    }
+
+    #[test]
+    fn labels() {
+        let label = |key| format!("{}", KeyLabel(key));
+        assert_eq!(label(DecodedKey::RawKey(KeyCode::Spacebar)), "Space");
+        assert_eq!(label(DecodedKey::Unicode('p')), "P");
+        assert_eq!(label(DecodedKey::RawKey(KeyCode::ArrowLeft)), "Left");
+        assert_eq!(label(DecodedKey::RawKey(KeyCode::Numpad4)), "4");
+    }
+}
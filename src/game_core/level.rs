@@ -0,0 +1,270 @@
+// Level descriptions: a short header of wave parameters followed by an ASCII
+// map of the board, e.g.
+//
+//   speed: 3
+//   shots: 1
+//   ---
+//   #....@..@..@....#
+//   ...
+//
+// speed is the number of updates the formation waits between steps and
+// shots is how many alien shots may be in flight at once. In the map '.' is
+// empty space, '|' the indestructible wall, '#' a bunker cell and '^' the
+// player; a digit from 1 to 4 is a bunker cell that starts with only that
+// many hits left. Aliens are placed by their icon: 'Y' squid, 'X' crab,
+// '@' octopus. The game lowers each wave's formation a row more than the
+// last, so the built-in levels all start theirs on the same row.
+
+use core::fmt;
+use super::{Aliens, Alien, AlienKind, Cell, Position, MAX_ALIENS, BARRIER_HEALTH};
+
+pub const LEVELS: [&str; 4] = [
+    include_str!("../../levels/wave1.txt"),
+    include_str!("../../levels/wave2.txt"),
+    include_str!("../../levels/wave3.txt"),
+    include_str!("../../levels/wave4.txt"),
+];
+
+const SEPARATOR: &str = "---";
+
+#[derive(Copy,Clone,Eq,PartialEq,Debug)]
+pub struct Level<const WIDTH: usize, const HEIGHT: usize> {
+    pub(super) cells: [[Cell; WIDTH]; HEIGHT],
+    pub(super) player: Position,
+    pub(super) aliens: Aliens,
+    pub(super) alien_move_frequency: usize,
+    pub(super) alien_shots: usize
+}
+
+// Rows and columns count from 1 and refer to the level text, header included
+#[derive(Copy,Clone,Eq,PartialEq,Debug)]
+pub struct LevelError {
+    pub row: usize,
+    pub col: usize,
+    pub kind: LevelErrorKind
+}
+
+#[derive(Copy,Clone,Eq,PartialEq,Debug)]
+pub enum LevelErrorKind {
+    BadHeaderLine,
+    UnknownSetting,
+    DuplicateSetting,
+    BadNumber,
+    MissingSetting(&'static str),
+    MissingMap,
+    MapTooWide(usize), // the board's width
+    MapTooTall(usize), // the board's height
+    TooManyAliens,
+    UnknownIcon(char),
+    MissingPlayer,
+    ExtraPlayer,
+    NoLevels // a game given an empty level table; row and column are 0
+}
+
+impl fmt::Display for LevelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.kind == LevelErrorKind::NoLevels {
+            return write!(f, "no levels")
+        }
+        write!(f, "row {}, column {}: ", self.row, self.col)?;
+        match self.kind {
+            LevelErrorKind::BadHeaderLine => write!(f, "expected 'setting: value' or '{}'", SEPARATOR),
+            LevelErrorKind::UnknownSetting => write!(f, "unknown setting"),
+            LevelErrorKind::DuplicateSetting => write!(f, "setting given twice"),
+            LevelErrorKind::BadNumber => write!(f, "expected a number"),
+            LevelErrorKind::MissingSetting(name) => write!(f, "missing '{}' setting", name),
+            LevelErrorKind::MissingMap => write!(f, "no map after the header"),
+            LevelErrorKind::MapTooWide(width) => write!(f, "map is wider than {} columns", width),
+            LevelErrorKind::MapTooTall(height) => write!(f, "map is taller than {} rows", height),
+            LevelErrorKind::TooManyAliens => write!(f, "more than {} aliens", MAX_ALIENS),
+            LevelErrorKind::UnknownIcon(icon) => write!(f, "unknown map character '{}'", icon),
+            LevelErrorKind::MissingPlayer => write!(f, "map has no player"),
+            LevelErrorKind::ExtraPlayer => write!(f, "map has more than one player"),
+            LevelErrorKind::NoLevels => write!(f, "no levels")
+        }
+    }
+}
+
+impl<const WIDTH: usize, const HEIGHT: usize> Level<WIDTH, HEIGHT> {
+    // Maps smaller than the board fill its top left corner
+    pub fn parse(text: &str) -> Result<Self, LevelError> {
+        let mut lines = text.lines().enumerate();
+        let mut speed = None;
+        let mut shots = None;
+        let mut map_start = None;
+        for (line_num, raw_line) in lines.by_ref() {
+            let row = line_num + 1;
+            let line = raw_line.trim();
+            if line == SEPARATOR {
+                map_start = Some(row + 1);
+                break;
+            }
+            if line.is_empty() {
+                continue;
+            }
+            let (name, value) = split_setting(line)
+                .ok_or(LevelError {row, col: 1, kind: LevelErrorKind::BadHeaderLine})?;
+            let value_col = value.as_ptr() as usize - raw_line.as_ptr() as usize + 1;
+            let number = value.parse::<usize>()
+                .map_err(|_| LevelError {row, col: value_col, kind: LevelErrorKind::BadNumber})?;
+            let setting = match name {
+                "speed" => &mut speed,
+                "shots" => &mut shots,
+                _ => return Err(LevelError {row, col: 1, kind: LevelErrorKind::UnknownSetting})
+            };
+            if setting.replace(number).is_some() {
+                return Err(LevelError {row, col: 1, kind: LevelErrorKind::DuplicateSetting});
+            }
+        }
+        let map_start = map_start
+            .ok_or(LevelError {row: text.lines().count() + 1, col: 1, kind: LevelErrorKind::MissingMap})?;
+        let missing = |name| LevelError {row: map_start - 1, col: 1, kind: LevelErrorKind::MissingSetting(name)};
+        let speed = speed.ok_or_else(|| missing("speed"))?;
+        let shots = shots.ok_or_else(|| missing("shots"))?;
+
+        let mut level = Level {
+            cells: [[Cell::Empty; WIDTH]; HEIGHT],
+            player: Position {row: 0, col: 0},
+            aliens: Aliens::new(),
+            alien_move_frequency: speed,
+            alien_shots: shots
+        };
+        let mut player = None;
+        let mut alien_count = 0;
+        for (map_row, (line_num, line)) in lines.enumerate() {
+            let row = line_num + 1;
+            if map_row >= HEIGHT {
+                if line.trim().is_empty() {
+                    continue;
+                }
+                return Err(LevelError {row, col: 1, kind: LevelErrorKind::MapTooTall(HEIGHT)});
+            }
+            for (map_col, icon) in line.chars().enumerate() {
+                let col = map_col + 1;
+                if map_col >= WIDTH {
+                    return Err(LevelError {row, col, kind: LevelErrorKind::MapTooWide(WIDTH)});
+                }
+                let pos = Position {row: map_row as i16, col: map_col as i16};
+                match icon {
+                    '.' => {},
+                    '|' => level.cells[map_row][map_col] = Cell::Wall,
+                    '#' => level.cells[map_row][map_col] = Cell::Barrier(BARRIER_HEALTH),
+                    '1'..='4' => level.cells[map_row][map_col] = Cell::Barrier(icon as u8 - b'0'),
+                    '^' => {
+                        if player.is_some() {
+                            return Err(LevelError {row, col, kind: LevelErrorKind::ExtraPlayer});
+                        }
+                        player = Some(pos);
+                    }
+                    _ => {
+                        let kind = AlienKind::from_icon(icon)
+                            .ok_or(LevelError {row, col, kind: LevelErrorKind::UnknownIcon(icon)})?;
+                        if alien_count == MAX_ALIENS {
+                            return Err(LevelError {row, col, kind: LevelErrorKind::TooManyAliens});
+                        }
+                        level.aliens.aliens[alien_count] = Alien::new(pos, kind);
+                        alien_count += 1;
+                    }
+                }
+            }
+        }
+        level.player = player
+            .ok_or(LevelError {row: map_start, col: 1, kind: LevelErrorKind::MissingPlayer})?;
+        Ok(level)
+    }
+
+    // The level for a wave from a table such as LEVELS, which repeats once
+    // it runs out. Waves count from 1; wave 0 gets the first level. The
+    // table must already have been checked, as SpaceInvadersGame::with_levels
+    // does, and LEVELS is by the tests.
+    pub fn for_wave(levels: &[&str], wave: usize) -> Self {
+        let index = wave.saturating_sub(1) % levels.len();
+        match Level::parse(levels[index]) {
+            Ok(level) => level,
+            Err(e) => unreachable!("level {} was checked, yet {}", index + 1, e)
+        }
+    }
+}
+
+fn split_setting(line: &str) -> Option<(&str, &str)> {
+    let colon = line.find(':')?;
+    Some((line[..colon].trim(), line[colon + 1..].trim()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_core::{BOARD_WIDTH as WIDTH, BOARD_HEIGHT as HEIGHT};
+
+    type Level = super::Level<WIDTH, HEIGHT>;
+
+    fn error_of(text: &str) -> LevelError {
+        Level::parse(text).unwrap_err()
+    }
+
+    #[test]
+    fn built_in_levels_parse() {
+        for wave in 1..=LEVELS.len() {
+            let level = Level::parse(LEVELS[wave - 1]).unwrap();
+            assert!(level.aliens.live().count() > 0, "wave {}", wave);
+        }
+    }
+
+    #[test]
+    fn small_map() {
+        let level = Level::parse("speed: 5\nshots: 2\n---\n.@Y\n#.2\n|^.").unwrap();
+        assert_eq!(level.alien_move_frequency, 5);
+        assert_eq!(level.alien_shots, 2);
+        assert_eq!(level.player, Position {row: 2, col: 1});
+        assert_eq!(level.aliens.live().map(|a| (a.pos, a.kind)).collect::<Vec<_>>(),
+                   vec![(Position {row: 0, col: 1}, AlienKind::Octopus), (Position {row: 0, col: 2}, AlienKind::Squid)]);
+        assert_eq!(level.cells[1][0], Cell::Barrier(BARRIER_HEALTH));
+        assert_eq!(level.cells[1][1], Cell::Empty);
+        assert_eq!(level.cells[1][2], Cell::Barrier(2));
+        assert_eq!(level.cells[2][0], Cell::Wall);
+        assert_eq!(level.cells[HEIGHT - 1][WIDTH - 1], Cell::Empty);
+    }
+
+    #[test]
+    fn reports_position_of_unknown_icon() {
+        assert_eq!(error_of("speed: 1\nshots: 1\n---\n...\n.^x"),
+                   LevelError {row: 5, col: 3, kind: LevelErrorKind::UnknownIcon('x')});
+    }
+
+    #[test]
+    fn reports_header_problems() {
+        assert_eq!(error_of("speed 1\n---\n^").kind, LevelErrorKind::BadHeaderLine);
+        assert_eq!(error_of("speed: fast\n---\n^"), LevelError {row: 1, col: 8, kind: LevelErrorKind::BadNumber});
+        assert_eq!(error_of("speed: 1\ncolour: 2\n---\n^").kind, LevelErrorKind::UnknownSetting);
+        assert_eq!(error_of("speed: 1\nshots: 1\nspeed: 2\n---\n^"), LevelError {row: 3, col: 1, kind: LevelErrorKind::DuplicateSetting});
+        assert_eq!(error_of("speed: 1\n---\n^").kind, LevelErrorKind::MissingSetting("shots"));
+        assert_eq!(error_of("speed: 1\nshots: 1\n").kind, LevelErrorKind::MissingMap);
+    }
+
+    #[test]
+    fn reports_map_problems() {
+        assert_eq!(error_of("speed: 1\nshots: 1\n---\n...").kind, LevelErrorKind::MissingPlayer);
+        assert_eq!(error_of("speed: 1\nshots: 1\n---\n^.^"), LevelError {row: 4, col: 3, kind: LevelErrorKind::ExtraPlayer});
+        let wide = "speed: 1\nshots: 1\n---\n^".to_string() + &".".repeat(WIDTH);
+        assert_eq!(error_of(&wide), LevelError {row: 4, col: WIDTH + 1, kind: LevelErrorKind::MapTooWide(WIDTH)});
+        let tall = "speed: 1\nshots: 1\n---\n^".to_string() + &"\n.".repeat(HEIGHT);
+        assert_eq!(error_of(&tall), LevelError {row: 4 + HEIGHT, col: 1, kind: LevelErrorKind::MapTooTall(HEIGHT)});
+        let crowded = "speed: 1\nshots: 1\n---\n^\n".to_string() + &("@".repeat(WIDTH) + "\n").repeat(MAX_ALIENS / WIDTH + 1);
+        assert_eq!(error_of(&crowded), LevelError {row: 5 + MAX_ALIENS / WIDTH, col: MAX_ALIENS % WIDTH + 1, kind: LevelErrorKind::TooManyAliens});
+    }
+
+    #[test]
+    fn wave_zero_is_the_first_level() {
+        assert_eq!(Level::for_wave(&LEVELS, 0), Level::for_wave(&LEVELS, 1));
+        assert_eq!(Level::for_wave(&LEVELS, LEVELS.len() + 1), Level::for_wave(&LEVELS, 1));
+    }
+
+    #[test]
+    fn maps_must_fit_the_board() {
+        let map = "speed: 1\nshots: 1\n---\n@.@\n.^.";
+        let level = super::Level::<3, 2>::parse(map).unwrap();
+        assert_eq!(level.player, Position {row: 1, col: 1});
+        assert_eq!(super::Level::<2, 2>::parse(map).unwrap_err().kind, LevelErrorKind::MapTooWide(2));
+        assert_eq!(super::Level::<3, 1>::parse(map).unwrap_err().kind, LevelErrorKind::MapTooTall(1));
+    }
+}
@@ -0,0 +1,72 @@
+// Which alien and which shot stand on each board cell, kept up to date as
+// they move so a cell can be looked up instead of searching every entity.
+// Aliens and shots have a layer each because a shot shares its target's cell
+// until the collision is resolved.
+
+use super::{Aliens, Position, MAX_ALIENS};
+use super::shots::{ShotPool, SHOT_POOL_SIZE};
+
+// Alien slots are stored as u8
+const _: () = assert!(MAX_ALIENS <= u8::MAX as usize + 1);
+
+// A slot in one of the two shot pools
+#[derive(Copy,Clone,Eq,PartialEq,Debug)]
+pub(super) enum ShotId {
+    Player(u8),
+    Alien(u8)
+}
+
+#[derive(Copy,Clone,Eq,PartialEq,Debug)]
+pub(super) struct Occupancy<const WIDTH: usize, const HEIGHT: usize> {
+    aliens: [[Option<u8>; WIDTH]; HEIGHT],
+    shots: [[Option<ShotId>; WIDTH]; HEIGHT],
+    placed_shots: [Option<Position>; 2 * SHOT_POOL_SIZE] // cells written to the shot layer
+}
+
+impl<const WIDTH: usize, const HEIGHT: usize> Occupancy<WIDTH, HEIGHT> {
+    pub(super) fn new() -> Self {
+        Occupancy {aliens: [[None; WIDTH]; HEIGHT], shots: [[None; WIDTH]; HEIGHT], placed_shots: [None; 2 * SHOT_POOL_SIZE]}
+    }
+
+    pub(super) fn alien_at(&self, p: Position) -> Option<usize> {
+        let (row, col) = p.row_col();
+        self.aliens[row][col].map(|i| i as usize)
+    }
+
+    pub(super) fn shot_at(&self, p: Position) -> Option<ShotId> {
+        let (row, col) = p.row_col();
+        self.shots[row][col]
+    }
+
+    // The whole formation moves at once, so its layer is laid out afresh
+    pub(super) fn place_aliens(&mut self, aliens: &Aliens) {
+        self.aliens = [[None; WIDTH]; HEIGHT];
+        for (i, alien) in aliens.aliens.iter().enumerate().filter(|(_, alien)| alien.alive) {
+            let (row, col) = alien.pos.row_col();
+            self.aliens[row][col] = Some(i as u8);
+        }
+    }
+
+    pub(super) fn remove_alien(&mut self, p: Position) {
+        let (row, col) = p.row_col();
+        self.aliens[row][col] = None;
+    }
+
+    // Clears the cells shots were last recorded in, then records the ones
+    // still in flight. Player shots go last so they win a shared cell.
+    pub(super) fn place_shots(&mut self, player_shots: &ShotPool, alien_shots: &ShotPool) {
+        for p in self.placed_shots.iter_mut().filter_map(|p| p.take()) {
+            let (row, col) = p.row_col();
+            self.shots[row][col] = None;
+        }
+        let alien_ids = alien_shots.shots.iter().enumerate().map(|(i, shot)| (ShotId::Alien(i as u8), shot));
+        let player_ids = player_shots.shots.iter().enumerate().map(|(i, shot)| (ShotId::Player(i as u8), shot));
+        for (placed, (id, shot)) in self.placed_shots.iter_mut().zip(alien_ids.chain(player_ids)) {
+            if shot.active {
+                let (row, col) = shot.pos.row_col();
+                self.shots[row][col] = Some(id);
+                *placed = Some(shot.pos);
+            }
+        }
+    }
+}
@@ -0,0 +1,459 @@
+// Input recording and replay. Once seeded, a game only changes through key
+// presses and timer ticks, so the seed, the starting high-score table and
+// every (tick, key) pair are enough to play a session back exactly. A
+// recording starts either at the title screen or just as a game begins, in
+// which case it also holds the number of players and the key bindings.
+//
+// Recordings travel as text, one item per line:
+//
+//   seed 5eed1978deadbeef
+//   high ABC 1200
+//   players 1
+//   bind 3 u77 u20
+//   12 u20
+//   40 +ArrowLeft
+//   40 kArrowLeft
+//   47 -ArrowLeft
+//   end 95
+//
+// high lines give the table best first. players is there for a recording
+// of a game already started. bind lines list an action's keys, the action
+// numbered as on the options screen, and appear only for bindings that
+// differ from the default. Each event line is the tick it arrived on and the
+// key: 'u' and a hex code point for a typed character, 'k' and a key name
+// for a typed raw key, or '+' or '-' and a key name for a key going down or
+// coming up. end is the number of ticks recorded. A
+// recording that has overwritten its oldest events says so with a
+// "dropped N" line and can no longer be replayed.
+
+use core::convert::TryInto;
+use core::fmt;
+use pc_keyboard::{DecodedKey, KeyEvent};
+use super::SpaceInvadersGame;
+use super::high_scores::{HighScores, INITIALS_LEN};
+use super::input::{KeyBindings, KeyInput, HeldKeys, ACTIONS, KEYS_PER_ACTION, key_name, key_code};
+
+pub const RECORDING_SIZE: usize = 1024;
+
+#[derive(Copy,Clone,Eq,PartialEq,Debug)]
+pub struct InputEvent {
+    pub tick: u64,
+    pub input: KeyInput
+}
+
+// A session's keys in a ring buffer; once full, each new key overwrites the
+// oldest
+#[derive(Copy,Clone,Eq,PartialEq,Debug)]
+pub struct Recording {
+    seed: u64,
+    high_scores: HighScores,
+    players: u8, // 0 for a recording that starts at the title screen
+    bindings: KeyBindings,
+    held: HeldKeys, // so the keyboard's repeats of a held key go in once
+    events: [InputEvent; RECORDING_SIZE],
+    start: usize,
+    len: usize,
+    dropped: u64,
+    ticks: u64
+}
+
+impl Recording {
+    // Starts recording a game given this seed and high-score table
+    pub fn new(seed: u64, high_scores: HighScores) -> Self {
+        Recording::begin(seed, high_scores, 0, KeyBindings::default())
+    }
+
+    // Starts recording a game a player has just started, reseeding it so
+    // its replay can be seeded the same way
+    pub fn new_game<const WIDTH: usize, const HEIGHT: usize>(seed: u64, game: &mut SpaceInvadersGame<WIDTH, HEIGHT>) -> Self {
+        game.seed(seed);
+        Recording::begin(seed, game.high_scores, game.players, game.bindings)
+    }
+
+    fn begin(seed: u64, high_scores: HighScores, players: u8, bindings: KeyBindings) -> Self {
+        let blank = InputEvent {tick: 0, input: KeyInput::Typed(DecodedKey::Unicode(' '))};
+        Recording {seed, high_scores, players, bindings, held: HeldKeys::default(), events: [blank; RECORDING_SIZE], start: 0, len: 0, dropped: 0, ticks: 0}
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn ticks(&self) -> u64 {
+        self.ticks
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    // False once the oldest events have been overwritten
+    pub fn is_complete(&self) -> bool {
+        self.dropped == 0
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item=&InputEvent> {
+        (0..self.len).map(move |i| &self.events[(self.start + i) % RECORDING_SIZE])
+    }
+
+    fn event(&self, i: usize) -> Option<&InputEvent> {
+        if i < self.len {Some(&self.events[(self.start + i) % RECORDING_SIZE])} else {None}
+    }
+
+    pub fn record(&mut self, key: DecodedKey) {
+        self.record_input(KeyInput::Typed(key));
+    }
+
+    pub fn record_event(&mut self, event: &KeyEvent) {
+        self.record_input(KeyInput::from(event));
+    }
+
+    pub fn record_input(&mut self, input: KeyInput) {
+        // Unnamed raw keys can't be written out, and no control uses them
+        if let KeyInput::Typed(DecodedKey::RawKey(code)) | KeyInput::Down(code) | KeyInput::Up(code) = input {
+            if key_name(code).is_none() {
+                return
+            }
+        }
+        match input {
+            KeyInput::Down(code) if self.held.is_down(code) => return,
+            KeyInput::Down(code) => self.held.press(code),
+            KeyInput::Up(code) => self.held.release(code),
+            KeyInput::Typed(_) => {}
+        }
+        let event = InputEvent {tick: self.ticks, input};
+        if self.len < RECORDING_SIZE {
+            self.events[(self.start + self.len) % RECORDING_SIZE] = event;
+            self.len += 1;
+        } else {
+            self.events[self.start] = event;
+            self.start = (self.start + 1) % RECORDING_SIZE;
+            self.dropped += 1;
+        }
+    }
+
+    // Called once per timer tick, after the game has seen it
+    pub fn tick(&mut self) {
+        self.ticks += 1;
+    }
+
+    // The game as it stood when recording began
+    pub fn start_game<const WIDTH: usize, const HEIGHT: usize>(&self) -> SpaceInvadersGame<WIDTH, HEIGHT> {
+        let mut game = SpaceInvadersGame::new();
+        game.set_high_scores(self.high_scores);
+        game.bindings = self.bindings;
+        if self.players > 0 {
+            game.start(self.players);
+            game.take_game_started();
+        }
+        game.seed(self.seed);
+        game
+    }
+
+    pub fn write_to<W: fmt::Write + ?Sized>(&self, out: &mut W) -> fmt::Result {
+        writeln!(out, "seed {:x}", self.seed)?;
+        for entry in self.high_scores.iter() {
+            writeln!(out, "high {} {}", entry.initials(), entry.score())?;
+        }
+        if self.players > 0 {
+            writeln!(out, "players {}", self.players)?;
+        }
+        if self.bindings != KeyBindings::default() {
+            for (i, action) in ACTIONS.iter().enumerate() {
+                write!(out, "bind {}", i + 1)?;
+                for key in self.bindings.keys(*action) {
+                    out.write_char(' ')?;
+                    write_key(out, key)?;
+                }
+                writeln!(out)?;
+            }
+        }
+        if self.dropped > 0 {
+            writeln!(out, "dropped {}", self.dropped)?;
+        }
+        for event in self.iter() {
+            let name = |code| key_name(code).unwrap_or("?");
+            match event.input {
+                KeyInput::Typed(key) => {
+                    write!(out, "{} ", event.tick)?;
+                    write_key(out, key)?;
+                    writeln!(out)?;
+                }
+                KeyInput::Down(code) => writeln!(out, "{} +{}", event.tick, name(code))?,
+                KeyInput::Up(code) => writeln!(out, "{} -{}", event.tick, name(code))?
+            }
+        }
+        writeln!(out, "end {}", self.ticks)
+    }
+
+    pub fn parse(text: &str) -> Result<Recording, LogError> {
+        let mut seed = None;
+        let mut high_scores = HighScores::new();
+        let mut players = 0;
+        let mut bindings = KeyBindings::default();
+        let mut recording = None;
+        let mut ticks = None;
+        for (i, line) in text.lines().enumerate() {
+            let err = |kind| LogError {line: i + 1, kind};
+            let line = line.trim();
+            if line.is_empty() {
+                continue
+            }
+            if ticks.is_some() {
+                return Err(err(LogErrorKind::AfterEnd))
+            }
+            let (first, rest) = line.split_once(' ').ok_or(err(LogErrorKind::BadLine))?;
+            match first {
+                "seed" if seed.is_none() => seed = Some(u64::from_str_radix(rest, 16).map_err(|_| err(LogErrorKind::BadNumber))?),
+                "high" if recording.is_none() => {
+                    let (initials, score) = rest.split_once(' ').ok_or(err(LogErrorKind::BadLine))?;
+                    let initials: [u8; INITIALS_LEN] = initials.as_bytes().try_into().map_err(|_| err(LogErrorKind::BadInitials))?;
+                    let score = score.parse().map_err(|_| err(LogErrorKind::BadNumber))?;
+                    high_scores.insert(initials, score);
+                }
+                "players" if recording.is_none() => {
+                    players = rest.parse().ok().filter(|players| (1..=2).contains(players)).ok_or(err(LogErrorKind::BadNumber))?;
+                }
+                "bind" if recording.is_none() => {
+                    let mut words = rest.split_whitespace();
+                    let action = words.next()
+                        .and_then(|number| number.parse::<usize>().ok())
+                        .and_then(|number| ACTIONS.get(number.wrapping_sub(1)))
+                        .ok_or(err(LogErrorKind::BadLine))?;
+                    let mut keys = [None; KEYS_PER_ACTION];
+                    for (i, word) in words.enumerate() {
+                        let slot = keys.get_mut(i).ok_or(err(LogErrorKind::BadLine))?;
+                        match parse_key(word) {
+                            Some(KeyInput::Typed(key)) => *slot = Some(key),
+                            _ => return Err(err(LogErrorKind::BadKey))
+                        }
+                    }
+                    bindings.set_keys(*action, keys);
+                }
+                "dropped" => return Err(err(LogErrorKind::Incomplete)),
+                "end" => ticks = Some(rest.parse().map_err(|_| err(LogErrorKind::BadNumber))?),
+                tick => {
+                    let tick: u64 = tick.parse().map_err(|_| err(LogErrorKind::BadLine))?;
+                    let key = parse_key(rest).ok_or(err(LogErrorKind::BadKey))?;
+                    let seed = seed.ok_or(err(LogErrorKind::MissingSeed))?;
+                    let recording = recording.get_or_insert_with(|| Recording::begin(seed, high_scores, players, bindings));
+                    if tick < recording.ticks {
+                        return Err(err(LogErrorKind::OutOfOrder))
+                    }
+                    if recording.len == RECORDING_SIZE {
+                        return Err(err(LogErrorKind::TooLong))
+                    }
+                    recording.ticks = tick;
+                    recording.record_input(key);
+                }
+            }
+        }
+        let ticks = ticks.ok_or(LogError {line: text.lines().count(), kind: LogErrorKind::MissingEnd})?;
+        let seed = seed.ok_or(LogError {line: 1, kind: LogErrorKind::MissingSeed})?;
+        let mut recording = recording.unwrap_or_else(|| Recording::begin(seed, high_scores, players, bindings));
+        if ticks < recording.ticks {
+            return Err(LogError {line: text.lines().count(), kind: LogErrorKind::OutOfOrder})
+        }
+        recording.ticks = ticks;
+        Ok(recording)
+    }
+}
+
+fn write_key<W: fmt::Write + ?Sized>(out: &mut W, key: DecodedKey) -> fmt::Result {
+    match key {
+        DecodedKey::Unicode(c) => write!(out, "u{:x}", c as u32),
+        DecodedKey::RawKey(code) => write!(out, "k{}", key_name(code).unwrap_or("?"))
+    }
+}
+
+fn parse_key(text: &str) -> Option<KeyInput> {
+    let mut chars = text.chars();
+    let kind = chars.next()?;
+    let rest = chars.as_str();
+    match kind {
+        'u' => u32::from_str_radix(rest, 16).ok().and_then(char::from_u32).map(|c| KeyInput::Typed(DecodedKey::Unicode(c))),
+        'k' => key_code(rest).map(|code| KeyInput::Typed(DecodedKey::RawKey(code))),
+        '+' => key_code(rest).map(KeyInput::Down),
+        '-' => key_code(rest).map(KeyInput::Up),
+        _ => None
+    }
+}
+
+// Lines count from 1
+#[derive(Copy,Clone,Eq,PartialEq,Debug)]
+pub struct LogError {
+    pub line: usize,
+    pub kind: LogErrorKind
+}
+
+#[derive(Copy,Clone,Eq,PartialEq,Debug)]
+pub enum LogErrorKind {
+    BadLine,
+    BadNumber,
+    BadInitials,
+    BadKey,
+    MissingSeed,
+    MissingEnd,
+    AfterEnd,
+    OutOfOrder,
+    TooLong,
+    Incomplete
+}
+
+impl fmt::Display for LogError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: ", self.line)?;
+        match self.kind {
+            LogErrorKind::BadLine => write!(f, "expected 'seed', 'high', 'players', 'bind', 'end' or an event"),
+            LogErrorKind::BadNumber => write!(f, "expected a number"),
+            LogErrorKind::BadInitials => write!(f, "initials must be {} letters", INITIALS_LEN),
+            LogErrorKind::BadKey => write!(f, "unknown key"),
+            LogErrorKind::MissingSeed => write!(f, "no seed before the first event"),
+            LogErrorKind::MissingEnd => write!(f, "no 'end' line"),
+            LogErrorKind::AfterEnd => write!(f, "text after the 'end' line"),
+            LogErrorKind::OutOfOrder => write!(f, "ticks go backwards"),
+            LogErrorKind::TooLong => write!(f, "more than {} events", RECORDING_SIZE),
+            LogErrorKind::Incomplete => write!(f, "the oldest events were overwritten, so it cannot be replayed")
+        }
+    }
+}
+
+// Plays a recording back into a game, one timer tick per step
+#[derive(Copy,Clone,Eq,PartialEq,Debug)]
+pub struct Replay {
+    recording: Recording,
+    next: usize,
+    tick: u64
+}
+
+impl Replay {
+    pub fn new(recording: Recording) -> Result<Replay, LogErrorKind> {
+        if recording.is_complete() {
+            Ok(Replay {recording, next: 0, tick: 0})
+        } else {
+            Err(LogErrorKind::Incomplete)
+        }
+    }
+
+    pub fn recording(&self) -> &Recording {
+        &self.recording
+    }
+
+    pub fn finished(&self) -> bool {
+        self.tick >= self.recording.ticks && self.next >= self.recording.len
+    }
+
+    // Feeds the game the keys that arrived before this tick, then the tick
+    // itself. Keys that arrived after the last tick get a step of their own
+    // with no tick. True if the game updated, as SpaceInvadersGame::tick.
+    pub fn step<const WIDTH: usize, const HEIGHT: usize>(&mut self, game: &mut SpaceInvadersGame<WIDTH, HEIGHT>) -> bool {
+        while let Some(event) = self.recording.event(self.next).filter(|event| event.tick == self.tick) {
+            game.input(event.input);
+            self.next += 1;
+        }
+        if self.tick == self.recording.ticks {
+            return false
+        }
+        self.tick += 1;
+        game.tick()
+    }
+
+    // Plays the rest of the recording into the game
+    pub fn run<const WIDTH: usize, const HEIGHT: usize>(&mut self, game: &mut SpaceInvadersGame<WIDTH, HEIGHT>) {
+        while !self.finished() {
+            self.step(game);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pc_keyboard::KeyCode;
+
+    fn sample() -> Recording {
+        let mut high_scores = HighScores::new();
+        high_scores.insert(*b"ABC", 1200);
+        let mut recording = Recording::new(0xBEEF, high_scores);
+        recording.tick();
+        recording.record(DecodedKey::Unicode('\n'));
+        recording.tick();
+        recording.tick();
+        recording.record_input(KeyInput::Down(KeyCode::ArrowLeft));
+        recording.record(DecodedKey::RawKey(KeyCode::ArrowLeft));
+        recording.record(DecodedKey::RawKey(KeyCode::PrintScreen));
+        recording.tick();
+        recording.record_input(KeyInput::Up(KeyCode::ArrowLeft));
+        recording
+    }
+
+    #[test]
+    fn text_round_trip() {
+        let mut text = String::new();
+        sample().write_to(&mut text).unwrap();
+        assert_eq!(text, "seed beef\nhigh ABC 1200\n1 ua\n3 +ArrowLeft\n3 kArrowLeft\n4 -ArrowLeft\nend 4\n");
+        assert_eq!(Recording::parse(&text), Ok(sample()));
+    }
+
+    #[test]
+    fn recordings_of_started_games_keep_players_and_bindings() {
+        let mut game: SpaceInvadersGame<80, 23> = SpaceInvadersGame::new();
+        game.key(DecodedKey::Unicode('o'));
+        game.key(DecodedKey::Unicode('w'));
+        game.key(DecodedKey::Unicode('\n'));
+        game.key(DecodedKey::Unicode('2'));
+        let mut recording = Recording::new_game(9, &mut game);
+        recording.tick();
+        recording.record(DecodedKey::Unicode('a'));
+        let mut text = String::new();
+        recording.write_to(&mut text).unwrap();
+        assert!(text.starts_with("seed 9\nplayers 2\nbind 1 u61\nbind 2 u64\nbind 3 u77 u20\n"), "{}", text);
+        assert!(text.ends_with("1 u61\nend 1\n"), "{}", text);
+        assert_eq!(Recording::parse(&text), Ok(recording));
+        let replayed: SpaceInvadersGame<80, 23> = recording.start_game();
+        assert_eq!((replayed.players(), replayed.bindings()), (2, game.bindings()));
+    }
+
+    #[test]
+    fn repeats_of_a_held_key_are_recorded_once() {
+        let mut recording = Recording::new(1, HighScores::new());
+        for _ in 0..3 {
+            recording.record_input(KeyInput::Down(KeyCode::ArrowLeft));
+            recording.tick();
+        }
+        recording.record_input(KeyInput::Up(KeyCode::ArrowLeft));
+        recording.record_input(KeyInput::Down(KeyCode::ArrowLeft));
+        let inputs: Vec<KeyInput> = recording.iter().map(|event| event.input).collect();
+        assert_eq!(inputs, vec![KeyInput::Down(KeyCode::ArrowLeft), KeyInput::Up(KeyCode::ArrowLeft), KeyInput::Down(KeyCode::ArrowLeft)]);
+    }
+
+    #[test]
+    fn full_recordings_keep_the_newest_keys() {
+        let mut recording = Recording::new(1, HighScores::new());
+        for i in 0..RECORDING_SIZE + 2 {
+            recording.record(DecodedKey::Unicode(char::from_u32(0x100 + i as u32).unwrap()));
+        }
+        assert_eq!(recording.len(), RECORDING_SIZE);
+        assert_eq!(recording.iter().next().unwrap().input, KeyInput::Typed(DecodedKey::Unicode('\u{102}')));
+        assert!(!recording.is_complete());
+        assert_eq!(Replay::new(recording), Err(LogErrorKind::Incomplete));
+        let mut text = String::new();
+        recording.write_to(&mut text).unwrap();
+        assert_eq!(Recording::parse(&text).unwrap_err().kind, LogErrorKind::Incomplete);
+    }
+
+    #[test]
+    fn bad_logs_are_rejected_with_the_line() {
+        assert_eq!(Recording::parse("seed 1\n5 kNoSuchKey\nend 6\n"), Err(LogError {line: 2, kind: LogErrorKind::BadKey}));
+        assert_eq!(Recording::parse("seed 1\n5 ua\n3 ua\nend 6\n"), Err(LogError {line: 3, kind: LogErrorKind::OutOfOrder}));
+        assert_eq!(Recording::parse("5 ua\nend 6\n"), Err(LogError {line: 1, kind: LogErrorKind::MissingSeed}));
+        assert_eq!(Recording::parse("seed 1\n5 ua\n").unwrap_err().kind, LogErrorKind::MissingEnd);
+        assert_eq!(Recording::parse("seed 1\nplayers 3\nend 6\n"), Err(LogError {line: 2, kind: LogErrorKind::BadNumber}));
+        assert_eq!(Recording::parse("seed 1\nbind 11 u61\nend 6\n"), Err(LogError {line: 2, kind: LogErrorKind::BadLine}));
+        assert_eq!(Recording::parse("seed 1\nbind 1 +A\nend 6\n"), Err(LogError {line: 2, kind: LogErrorKind::BadKey}));
+    }
+}
@@ -0,0 +1,69 @@
+// A small seedable xorshift64* generator. The kernel seeds it from the time
+// stamp counter at boot; tests keep the fixed default so every run plays the
+// same game.
+
+pub const DEFAULT_SEED: u64 = 0x5EED_1978_DEAD_BEEF;
+
+#[derive(Copy,Clone,Eq,PartialEq,Debug)]
+pub struct Rng {
+    state: u64
+}
+
+impl Default for Rng {
+    fn default() -> Self {
+        Rng::new(DEFAULT_SEED)
+    }
+}
+
+impl Rng {
+    // xorshift never leaves an all-zero state, so zero gets the default seed
+    pub fn new(seed: u64) -> Self {
+        Rng {state: if seed == 0 {DEFAULT_SEED} else {seed}}
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        let mut x = self.state;
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        self.state = x;
+        x.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    // A number in 0..n; the modulo bias is far too small to matter here
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_seed_same_numbers() {
+        let mut a = Rng::new(42);
+        let mut b = Rng::new(42);
+        let mut c = Rng::new(43);
+        let from_a: Vec<u64> = (0..10).map(|_| a.next_u64()).collect();
+        assert_eq!(from_a, (0..10).map(|_| b.next_u64()).collect::<Vec<_>>());
+        assert_ne!(from_a, (0..10).map(|_| c.next_u64()).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn zero_seed_still_varies() {
+        let mut rng = Rng::new(0);
+        assert_eq!(rng, Rng::default());
+        assert_ne!(rng.next_u64(), rng.next_u64());
+    }
+
+    #[test]
+    fn below_covers_the_whole_range() {
+        let mut rng = Rng::default();
+        let mut seen = [false; 6];
+        for _ in 0..200 {
+            seen[rng.below(seen.len())] = true;
+        }
+        assert!(seen.iter().all(|s| *s));
+    }
+}
@@ -0,0 +1,179 @@
+// Projectiles. The player and the aliens each fire from their own pool, so
+// neither side can use up the other's shots, and how many are in flight is
+// always counted from the pool itself.
+//
+// Speeds are in rows per SPEED_SCALE updates, which lets alien bombs fall at
+// half the speed of the player's laser. A shot covering several rows in one
+// update moves a row at a time so collisions are checked at every row.
+
+use super::{Dir, Position};
+
+pub const SHOT_POOL_SIZE: usize = 4;
+pub const PLAYER_SHOTS: usize = 3;
+const SPEED_SCALE: u8 = 2;
+pub(super) const MAX_ROWS_PER_UPDATE: usize = 2;
+
+// The player's laser, and the arcade's three bombs: a plain rolling shot, a
+// squiggly one that weaves from side to side and a fast plunger
+#[derive(Debug,Copy,Clone,Eq,PartialEq)]
+pub enum ShotKind {
+    Laser,
+    Straight,
+    ZigZag,
+    Plunger
+}
+
+impl ShotKind {
+    // Rows per SPEED_SCALE updates; none may exceed MAX_ROWS_PER_UPDATE rows an update
+    fn speed(&self) -> u8 {
+        match self {
+            ShotKind::Laser => 2,
+            ShotKind::Straight | ShotKind::ZigZag => 1,
+            ShotKind::Plunger => 4
+        }
+    }
+}
+
+#[derive(Copy,Clone,Eq,PartialEq,Debug)]
+pub struct Shot {
+    pub(super) pos: Position,
+    prev: Position, // where it was before its last step, to catch shots passing each other
+    pub(super) active: bool,
+    dir: Dir,
+    kind: ShotKind,
+    zag: bool, // which way a zig-zag shot steps next
+    progress: u8, // speed banked toward the next row
+    rows_left: u8 // rows still to move this update
+}
+
+impl Shot {
+    fn new() -> Self {
+        let pos = Position {row: 0, col: 0};
+        Shot {pos, prev: pos, active: false, dir: Dir::N, kind: ShotKind::Laser, zag: false, progress: 0, rows_left: 0}
+    }
+
+    fn fire(&mut self, pos: Position, dir: Dir, kind: ShotKind) {
+        *self = Shot {pos, prev: pos, active: true, dir, kind, ..Shot::new()};
+    }
+
+    pub(super) fn deactivate(&mut self) { self.active = false;}
+
+    fn next_pos<const WIDTH: usize, const HEIGHT: usize>(&self) -> Position {
+        let ahead = self.pos.neighbor(self.dir);
+        match self.kind {
+            ShotKind::ZigZag => {
+                let side = if self.zag {ahead.neighbor(self.dir.right())} else {ahead.neighbor(self.dir.left())};
+                if side.is_legal::<WIDTH, HEIGHT>() {side} else {ahead}
+            }
+            _ => ahead
+        }
+    }
+
+    fn start_update(&mut self) {
+        self.progress += self.kind.speed();
+        self.rows_left = self.progress / SPEED_SCALE;
+        self.progress %= SPEED_SCALE;
+    }
+
+    // Moves a row if this update's movement isn't used up; shots leaving the
+    // board are gone
+    fn step<const WIDTH: usize, const HEIGHT: usize>(&mut self) {
+        self.prev = self.pos;
+        if self.rows_left > 0 {
+            self.rows_left -= 1;
+            let next = self.next_pos::<WIDTH, HEIGHT>();
+            if next.is_legal::<WIDTH, HEIGHT>() {
+                self.pos = next;
+                self.zag = !self.zag;
+            } else {
+                self.deactivate();
+            }
+        }
+    }
+
+    // True if the two share a cell or just swapped cells
+    pub(super) fn met(&self, other: &Shot) -> bool {
+        self.pos == other.pos || (self.pos == other.prev && self.prev == other.pos)
+    }
+
+    pub fn kind(&self) -> ShotKind {
+        self.kind
+    }
+
+    pub fn pos(&self) -> Position {
+        self.pos
+    }
+
+    pub fn icon(&self) -> char {
+        match self.kind {
+            ShotKind::Laser | ShotKind::Straight => '|',
+            ShotKind::ZigZag if self.zag => '\\',
+            ShotKind::ZigZag => '/',
+            ShotKind::Plunger => '!'
+        }
+    }
+}
+
+#[derive(Copy,Clone,Eq,PartialEq,Debug)]
+pub struct ShotPool {
+    pub(super) shots: [Shot; SHOT_POOL_SIZE],
+    capacity: usize // shots allowed in flight at once
+}
+
+impl ShotPool {
+    pub(super) fn new(capacity: usize) -> Self {
+        ShotPool {shots: [Shot::new(); SHOT_POOL_SIZE], capacity: capacity.min(SHOT_POOL_SIZE)}
+    }
+
+    pub(super) fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub(super) fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity.min(SHOT_POOL_SIZE);
+    }
+
+    pub fn active(&self) -> impl Iterator<Item=&Shot> {
+        self.shots.iter().filter(|shot| shot.active)
+    }
+
+    pub fn active_count(&self) -> usize {
+        self.active().count()
+    }
+
+    pub(super) fn can_fire(&self) -> bool {
+        self.active_count() < self.capacity
+    }
+
+    // False if the pool is already at capacity
+    pub(super) fn fire(&mut self, pos: Position, dir: Dir, kind: ShotKind) -> bool {
+        if !self.can_fire() {
+            return false
+        }
+        match self.shots.iter_mut().find(|shot| !shot.active) {
+            Some(shot) => {
+                shot.fire(pos, dir, kind);
+                true
+            }
+            None => false
+        }
+    }
+
+    pub(super) fn clear(&mut self) {
+        for shot in self.shots.iter_mut() {
+            shot.deactivate();
+        }
+    }
+
+    pub(super) fn start_update(&mut self) {
+        for shot in self.shots.iter_mut().filter(|shot| shot.active) {
+            shot.start_update();
+        }
+    }
+
+    pub(super) fn step<const WIDTH: usize, const HEIGHT: usize>(&mut self) {
+        for shot in self.shots.iter_mut().filter(|shot| shot.active) {
+            shot.step::<WIDTH, HEIGHT>();
+        }
+    }
+}
@@ -0,0 +1,188 @@
+// Headless driver for SpaceInvadersGame: feeds scripted keys and updates
+// without a timer or a screen, so tests can assert on the resulting state.
+
+use pc_keyboard::{DecodedKey, KeyCode};
+use super::input::KeyInput;
+use super::replay::{Recording, Replay};
+use super::{SpaceInvadersGame, Aliens, Alien, AlienKind, Player, Position, Cell, RESPAWN_TIME, BARRIER_HEALTH};
+use super::{BOARD_WIDTH as WIDTH, BOARD_HEIGHT as HEIGHT};
+
+// Tests play on the kernel's board unless they say otherwise
+pub(crate) type Game = SpaceInvadersGame<WIDTH, HEIGHT>;
+
+#[derive(Copy, Clone, Debug)]
+pub(crate) enum Input {
+    Key(DecodedKey),
+    Hold(KeyCode),
+    Release(KeyCode),
+    Updates(usize)
+}
+
+pub(crate) const LEFT: Input = Input::Key(DecodedKey::RawKey(KeyCode::ArrowLeft));
+pub(crate) const RIGHT: Input = Input::Key(DecodedKey::RawKey(KeyCode::ArrowRight));
+pub(crate) const FIRE: Input = Input::Key(DecodedKey::Unicode(' '));
+pub(crate) const RESTART: Input = Input::Key(DecodedKey::Unicode('s'));
+pub(crate) const START: Input = Input::Key(DecodedKey::Unicode('\n'));
+pub(crate) const PAUSE: Input = Input::Key(DecodedKey::Unicode('p'));
+pub(crate) const TWO_PLAYERS: Input = Input::Key(DecodedKey::Unicode('2'));
+
+pub(crate) struct Sim {
+    pub(crate) game: Game,
+    pub(crate) updates: usize
+}
+
+impl Sim {
+    // The standard opening board, started from the title screen
+    pub(crate) fn new() -> Self {
+        let mut sim = Sim {game: Game::new(), updates: 0};
+        sim.play(&[START]);
+        sim
+    }
+
+    // The opening board of a two-player game, player 1 to go first
+    pub(crate) fn two_players() -> Self {
+        let mut sim = Sim {game: Game::new(), updates: 0};
+        sim.play(&[TWO_PLAYERS]);
+        sim
+    }
+
+    // Plays out the current player's death up to the next turn
+    pub(crate) fn lose_turn(&mut self) -> &mut Self {
+        self.game.player_hit();
+        self.run(RESPAWN_TIME + 1)
+    }
+
+    // An empty board with the player at the bottom centre and no aliens;
+    // tests populate it with the place_* methods
+    pub(crate) fn blank() -> Self {
+        let mut sim = Sim::new();
+        sim.game.cells = [[Cell::Empty; WIDTH]; HEIGHT];
+        sim.game.aliens = Aliens::new();
+        sim.game.player = Player::new(pos(HEIGHT - 2, WIDTH / 2));
+        sim.game.occupancy.place_aliens(&sim.game.aliens);
+        sim
+    }
+
+    pub(crate) fn place_alien(&mut self, row: usize, col: usize) -> &mut Self {
+        self.place_alien_kind(row, col, AlienKind::Octopus)
+    }
+
+    pub(crate) fn place_alien_kind(&mut self, row: usize, col: usize, kind: AlienKind) -> &mut Self {
+        let slot = self.game.aliens.aliens.iter_mut()
+            .find(|alien| !alien.alive)
+            .expect("formation is full");
+        *slot = Alien::new(pos(row, col), kind);
+        self.game.occupancy.place_aliens(&self.game.aliens);
+        self
+    }
+
+    pub(crate) fn kill_aliens<F: Fn((usize, usize)) -> bool>(&mut self, doomed: F) -> &mut Self {
+        for alien in self.game.aliens.live_mut() {
+            if doomed(alien.pos.row_col()) {
+                alien.alive = false;
+            }
+        }
+        self.game.occupancy.place_aliens(&self.game.aliens);
+        self
+    }
+
+    pub(crate) fn place_barrier(&mut self, row: usize, col: usize) -> &mut Self {
+        self.game.cells[row][col] = Cell::Barrier(BARRIER_HEALTH);
+        self
+    }
+
+    pub(crate) fn hold_alien_fire(&mut self) -> &mut Self {
+        self.game.alien_shots.set_capacity(0);
+        self
+    }
+
+    pub(crate) fn hold_formation(&mut self) -> &mut Self {
+        self.game.alien_move_frequency = usize::MAX;
+        self.game.alien_move_countdown = usize::MAX;
+        self
+    }
+
+    // Plays out the player's last death up to the end of the game
+    pub(crate) fn lose_last_life(&mut self) -> &mut Self {
+        self.game.lives = 1;
+        self.game.player_hit();
+        self.run(RESPAWN_TIME + 1)
+    }
+
+    pub(crate) fn type_text(&mut self, text: &str) -> &mut Self {
+        for c in text.chars() {
+            self.key(DecodedKey::Unicode(c));
+        }
+        self
+    }
+
+    // The game a recorded session ends in, played back from its exported log
+    pub(crate) fn replay(log: &str) -> Self {
+        let recording = Recording::parse(log).unwrap_or_else(|e| panic!("bad log: {}", e));
+        let mut game = recording.start_game();
+        let mut replay = Replay::new(recording).unwrap();
+        replay.run(&mut game);
+        Sim {game, updates: 0}
+    }
+
+    pub(crate) fn key(&mut self, key: DecodedKey) -> &mut Self {
+        self.game.key(key);
+        self
+    }
+
+    pub(crate) fn run(&mut self, updates: usize) -> &mut Self {
+        for _ in 0..updates {
+            self.game.update();
+            self.updates += 1;
+        }
+        self
+    }
+
+    pub(crate) fn play(&mut self, script: &[Input]) -> &mut Self {
+        for input in script {
+            match input {
+                Input::Key(key) => {self.key(*key);},
+                Input::Hold(code) => self.game.input(KeyInput::Down(*code)),
+                Input::Release(code) => self.game.input(KeyInput::Up(*code)),
+                Input::Updates(n) => {self.run(*n);}
+            }
+        }
+        self
+    }
+
+    // Runs updates until the predicate holds, returning how many it took
+    pub(crate) fn run_until<F: Fn(&Game) -> bool>(&mut self, limit: usize, done: F) -> Option<usize> {
+        for n in 0..limit {
+            if done(&self.game) {
+                return Some(n)
+            }
+            self.run(1);
+        }
+        None
+    }
+
+    pub(crate) fn player_pos(&self) -> (usize, usize) {
+        self.game.player.pos.row_col()
+    }
+
+    // (player shots, alien shots) in flight
+    pub(crate) fn shot_counts(&self) -> (usize, usize) {
+        (self.game.player_shots.active_count(), self.game.alien_shots.active_count())
+    }
+
+    pub(crate) fn active_shot_positions(&self) -> impl Iterator<Item=(usize, usize)> + '_ {
+        self.game.player_shots.active().chain(self.game.alien_shots.active()).map(|s| s.pos.row_col())
+    }
+
+    pub(crate) fn alien_positions(&self) -> impl Iterator<Item=(usize, usize)> + '_ {
+        self.game.aliens.live().map(|a| a.pos.row_col())
+    }
+
+    pub(crate) fn live_aliens(&self) -> usize {
+        self.alien_positions().count()
+    }
+}
+
+pub(crate) fn pos(row: usize, col: usize) -> Position {
+    Position {row: row as i16, col: col as i16}
+}
@@ -0,0 +1,1084 @@
+use super::*;
+use super::sim::*;
+use super::{BOARD_WIDTH as WIDTH, BOARD_HEIGHT as HEIGHT};
+use pc_keyboard::KeyCode;
+
+#[test]
+fn opening_board() {
+    let sim = Sim::new();
+    assert_eq!(sim.game.status(), Status::Normal);
+    assert_eq!(sim.game.score(), 0);
+    assert_eq!(sim.game.wave(), 1);
+    assert_eq!(sim.player_pos(), (21, 36));
+    assert_eq!(sim.live_aliens(), 120);
+    assert_eq!(sim.shot_counts(), (0, 0));
+}
+
+#[test]
+fn player_moves_one_column_per_key() {
+    let mut sim = Sim::blank();
+    sim.place_alien(1, 2);
+    let (row, col) = sim.player_pos();
+    sim.play(&[LEFT, Input::Updates(1), LEFT, Input::Updates(1)]);
+    assert_eq!(sim.player_pos(), (row, col - 2));
+    sim.play(&[RIGHT, Input::Updates(1)]);
+    assert_eq!(sim.player_pos(), (row, col - 1));
+    // Each key press is consumed by a single update
+    sim.run(5);
+    assert_eq!(sim.player_pos(), (row, col - 1));
+}
+
+#[test]
+fn player_cannot_walk_into_barrier() {
+    let mut sim = Sim::blank();
+    let (row, col) = sim.player_pos();
+    sim.place_alien(1, 2).place_barrier(row, col - 1);
+    sim.play(&[LEFT, Input::Updates(1)]);
+    assert_eq!(sim.player_pos(), (row, col));
+}
+
+#[test]
+fn player_shot_travels_up_and_leaves_the_board() {
+    let mut sim = Sim::blank();
+    sim.place_alien(1, 2).hold_alien_fire();
+    let (row, col) = sim.player_pos();
+    // Fired from the row above the player, then moved in the same update
+    sim.play(&[FIRE, Input::Updates(1)]);
+    assert_eq!(sim.shot_counts(), (1, 0));
+    assert_eq!(sim.active_shot_positions().collect::<Vec<_>>(), vec![(row - 2, col)]);
+    sim.run(1);
+    assert_eq!(sim.active_shot_positions().collect::<Vec<_>>(), vec![(row - 3, col)]);
+    sim.run(row - 3);
+    assert_eq!(sim.active_shot_positions().collect::<Vec<_>>(), vec![(0, col)]);
+    sim.run(1);
+    assert_eq!(sim.shot_counts(), (0, 0));
+    assert_eq!(sim.active_shot_positions().count(), 0);
+}
+
+#[test]
+fn player_shots_are_limited() {
+    let mut sim = Sim::blank();
+    sim.place_alien(1, 2).hold_alien_fire();
+    for _ in 0..5 {
+        sim.play(&[FIRE, Input::Updates(1)]);
+    }
+    assert_eq!(sim.shot_counts(), (3, 0));
+    assert_eq!(sim.active_shot_positions().count(), 3);
+}
+
+#[test]
+fn player_shot_destroys_alien() {
+    let mut sim = Sim::blank();
+    let (row, col) = sim.player_pos();
+    sim.place_alien(row - 4, col).place_alien(1, 2).hold_alien_fire();
+    sim.play(&[FIRE, Input::Updates(3)]);
+    assert_eq!(sim.game.score(), AlienKind::Octopus.points());
+    assert_eq!(sim.live_aliens(), 1);
+    assert_eq!(sim.shot_counts(), (0, 0));
+    assert_eq!(sim.game.status(), Status::Normal);
+}
+
+#[test]
+fn player_shots_wear_barriers_down() {
+    let mut sim = Sim::blank();
+    let (row, col) = sim.player_pos();
+    sim.place_alien(1, 2).place_barrier(row - 3, col).place_barrier(row - 4, col).hold_alien_fire();
+    for health in (0..BARRIER_HEALTH).rev() {
+        sim.play(&[FIRE, Input::Updates(2)]);
+        assert_eq!(sim.shot_counts(), (0, 0));
+        let worn = if health == 0 {Cell::Empty} else {Cell::Barrier(health)};
+        assert_eq!(sim.game.cell(pos(row - 3, col)), worn);
+    }
+    assert_eq!(sim.game.cell(pos(row - 4, col)), Cell::Barrier(BARRIER_HEALTH));
+    sim.play(&[FIRE, Input::Updates(3)]);
+    assert_eq!(sim.game.cell(pos(row - 4, col)), Cell::Barrier(BARRIER_HEALTH - 1));
+    assert_eq!(sim.shot_counts(), (0, 0));
+}
+
+#[test]
+fn alien_shots_wear_barriers_and_splash_their_neighbours() {
+    let mut sim = Sim::blank();
+    let (_, col) = sim.player_pos();
+    sim.place_alien(5, col).place_barrier(10, col).place_barrier(10, col - 1).place_barrier(10, col + 1);
+    sim.place_barrier(11, col).place_barrier(12, col).place_barrier(9, col - 1);
+    sim.run(1);
+    sim.hold_alien_fire().run(7);
+    assert_eq!(sim.shot_counts(), (0, 0));
+    assert_eq!(sim.game.status(), Status::Normal);
+    for (row, col) in [(10, col - 1), (10, col), (10, col + 1), (11, col)] {
+        assert_eq!(sim.game.cell(pos(row, col)), Cell::Barrier(BARRIER_HEALTH - 1), "({}, {})", row, col);
+    }
+    for (row, col) in [(9, col - 1), (12, col)] {
+        assert_eq!(sim.game.cell(pos(row, col)), Cell::Barrier(BARRIER_HEALTH), "({}, {})", row, col);
+    }
+}
+
+#[test]
+fn walls_stop_shots_without_wearing() {
+    let mut sim = Sim::blank();
+    let (row, col) = sim.player_pos();
+    sim.place_alien(1, 2).hold_alien_fire();
+    sim.game.cells[row - 3][col] = Cell::Wall;
+    for _ in 0..BARRIER_HEALTH + 1 {
+        sim.play(&[FIRE, Input::Updates(2)]);
+        assert_eq!(sim.shot_counts(), (0, 0));
+    }
+    assert_eq!(sim.game.cell(pos(row - 3, col)), Cell::Wall);
+}
+
+#[test]
+fn descending_aliens_crush_bunkers() {
+    let mut sim = Sim::blank();
+    sim.place_alien(5, 10).place_barrier(5, 11).place_barrier(6, 11).hold_alien_fire();
+    sim.game.move_aliens();
+    assert_eq!(sim.alien_positions().collect::<Vec<_>>(), vec![(5, 11)]);
+    assert_eq!(sim.game.cell(pos(5, 11)), Cell::Empty);
+    assert_eq!(sim.game.cell(pos(6, 11)), Cell::Barrier(BARRIER_HEALTH));
+}
+
+// Wave 4's bunkers stand above the invasion row, so a formation left alone
+// reaches them before it lands
+#[test]
+fn an_unopposed_formation_crushes_wave_four_bunkers() {
+    let mut sim = Sim::new();
+    sim.game.set_wave(4);
+    sim.hold_alien_fire();
+    let bunkers = |game: &Game| game.cell_pos_iter().filter(|p| matches!(game.cell(*p), Cell::Barrier(_))).count();
+    let built = bunkers(&sim.game);
+    let crushed = sim.run_until(5000, |game| bunkers(game) < built || game.status() != Status::Normal);
+    assert!(crushed.is_some());
+    assert_eq!(sim.game.status(), Status::Normal);
+    assert!(bunkers(&sim.game) < built);
+}
+
+#[test]
+fn aliens_fire_from_the_bottom_of_a_column() {
+    let mut sim = Sim::new();
+    let aliens: Vec<(usize, usize)> = sim.alien_positions().collect();
+    for _ in 0..20 {
+        sim.game.aliens.fire_countdown = 0;
+        sim.game.clear_shots();
+        sim.game.alien_shoot();
+        let (row, col) = sim.active_shot_positions().next().unwrap();
+        assert!(aliens.contains(&(row - 1, col)));
+        assert!(aliens.iter().all(|(alien_row, alien_col)| *alien_col != col || *alien_row < row));
+    }
+}
+
+// The player's column is the likeliest to be fired on, so it is the one emptied
+#[test]
+fn destroyed_column_stops_shooting() {
+    let mut sim = Sim::new();
+    sim.game.seed(3);
+    let (_, col) = sim.player_pos();
+    sim.kill_aliens(|(_, alien_col)| alien_col == col);
+    assert!(sim.alien_positions().any(|(_, alien_col)| alien_col == col + 1));
+    for _ in 0..500 {
+        sim.game.aliens.fire_countdown = 0;
+        sim.game.clear_shots();
+        sim.game.alien_shoot();
+        let (_, shot_col) = sim.active_shot_positions().next().unwrap();
+        assert_ne!(shot_col, col);
+    }
+}
+
+#[test]
+fn alien_fire_favours_the_players_column() {
+    let mut sim = Sim::blank();
+    let (_, col) = sim.player_pos();
+    sim.place_alien(3, col).place_alien(3, 2).place_alien(3, WIDTH - 3).hold_formation();
+    let mut above = 0;
+    for _ in 0..100 {
+        sim.game.aliens.fire_countdown = 0;
+        sim.game.clear_shots();
+        sim.game.alien_shoot();
+        if sim.active_shot_positions().all(|(_, shot_col)| shot_col == col) {
+            above += 1;
+        }
+    }
+    assert!(above > 80, "{} of 100 shots from above the player", above);
+    assert!(above < 100);
+}
+
+#[test]
+fn seed_decides_the_fire_pattern() {
+    let shots_with = |seed: u64| {
+        let mut sim = Sim::new();
+        sim.game.seed(seed);
+        sim.hold_formation();
+        (0..20).map(|_| {
+            sim.run(1);
+            sim.active_shot_positions().collect::<Vec<_>>()
+        }).collect::<Vec<_>>()
+    };
+    assert_eq!(shots_with(7), shots_with(7));
+    assert_ne!(shots_with(7), shots_with(8));
+}
+
+#[test]
+fn zig_zag_shots_weave_down() {
+    let mut sim = Sim::blank();
+    sim.place_alien_kind(3, 10, AlienKind::Crab).hold_formation();
+    sim.run(2);
+    let mut path = vec![];
+    for _ in 0..4 {
+        path.extend(sim.active_shot_positions());
+        sim.hold_alien_fire().run(2);
+    }
+    assert_eq!(path, vec![(5, 11), (6, 10), (7, 11), (8, 10)]);
+}
+
+#[test]
+fn plunger_shots_fall_two_rows_an_update_without_skipping_barriers() {
+    let mut sim = Sim::blank();
+    sim.place_alien_kind(3, 10, AlienKind::Squid).place_barrier(8, 10).hold_formation();
+    sim.run(1);
+    assert_eq!(sim.active_shot_positions().collect::<Vec<_>>(), vec![(6, 10)]);
+    sim.hold_alien_fire().run(1);
+    assert_eq!(sim.game.cell(pos(8, 10)), Cell::Barrier(BARRIER_HEALTH - 1));
+    assert_eq!(sim.shot_counts(), (0, 0));
+}
+
+#[test]
+fn alien_shot_costs_a_life_and_freezes_the_board() {
+    let mut sim = Sim::blank();
+    let (row, col) = sim.player_pos();
+    sim.place_alien(row - 6, col).place_alien(1, 2);
+    // Bombs fall a row every other update
+    assert_eq!(sim.run_until(20, |g| g.status() == Status::Dying), Some(10));
+    assert_eq!(sim.game.lives(), STARTING_LIVES - 1);
+    assert_eq!(sim.shot_counts(), (0, 0));
+    assert_eq!(sim.active_shot_positions().count(), 0);
+    assert!(sim.game.player_exploding());
+
+    let aliens: Vec<(usize, usize)> = sim.alien_positions().collect();
+    let ufo_countdown = sim.game.ufo().countdown;
+    sim.play(&[LEFT, FIRE, Input::Updates(RESPAWN_TIME)]);
+    assert_eq!(sim.game.status(), Status::Dying);
+    assert!(!sim.game.player_exploding());
+    assert_eq!(sim.alien_positions().collect::<Vec<_>>(), aliens);
+    assert_eq!(sim.game.ufo().countdown, ufo_countdown);
+    assert_eq!(sim.player_pos(), (row, col));
+    assert_eq!(sim.shot_counts(), (0, 0));
+    sim.run(1);
+    assert_eq!(sim.game.status(), Status::Normal);
+}
+
+#[test]
+fn losing_the_last_life_ends_the_game_and_s_restarts() {
+    let mut sim = Sim::blank();
+    let (row, col) = sim.player_pos();
+    sim.place_alien(row - 6, col);
+    sim.game.lives = 1;
+    assert_eq!(sim.run_until(20, |g| g.status() == Status::Dying), Some(10));
+    assert_eq!(sim.game.lives(), 0);
+    sim.run(RESPAWN_TIME + 1);
+    assert_eq!(sim.game.status(), Status::Over);
+    sim.play(&[LEFT, Input::Updates(1)]);
+    assert_eq!(sim.player_pos(), (row, col));
+    sim.play(&[RESTART]);
+    assert_eq!(sim.game.status(), Status::Normal);
+    assert_eq!(sim.game.score(), 0);
+    assert_eq!(sim.game.lives(), STARTING_LIVES);
+    assert_eq!(sim.live_aliens(), 120);
+    assert_eq!(sim.shot_counts(), (0, 0));
+}
+
+#[test]
+fn extra_lives_at_score_thresholds() {
+    let mut game = Game::with_lives(2);
+    game.add_score(EXTRA_LIFE_SCORE - 10);
+    assert_eq!(game.lives(), 2);
+    game.add_score(10);
+    assert_eq!(game.lives(), 3);
+    game.add_score(EXTRA_LIFE_SCORE * 2);
+    assert_eq!(game.lives(), 5);
+    game.add_score(EXTRA_LIFE_SCORE * 10);
+    assert_eq!(game.lives(), MAX_LIVES);
+}
+
+#[test]
+fn formation_bounces_off_walls() {
+    let mut sim = Sim::new();
+    let start: Vec<(usize, usize)> = sim.alien_positions().collect();
+    let rightmost = start.iter().map(|(_, col)| *col).max().unwrap();
+    let steps = WIDTH - 1 - rightmost;
+    for _ in 0..steps {
+        sim.game.move_aliens();
+    }
+    let shifted: Vec<(usize, usize)> = start.iter().map(|(row, col)| (*row, col + steps)).collect();
+    assert_eq!(sim.alien_positions().collect::<Vec<_>>(), shifted);
+
+    // The next move would leave the board, so the formation drops and turns around
+    sim.game.move_aliens();
+    let dropped: Vec<(usize, usize)> = shifted.iter().map(|(row, col)| (row + 1, *col)).collect();
+    assert_eq!(sim.alien_positions().collect::<Vec<_>>(), dropped);
+    assert_eq!(sim.game.aliens.dir, Dir::W);
+
+    sim.game.move_aliens();
+    let back: Vec<(usize, usize)> = dropped.iter().map(|(row, col)| (*row, col - 1)).collect();
+    assert_eq!(sim.alien_positions().collect::<Vec<_>>(), back);
+}
+
+#[test]
+fn formation_reaching_last_row_ends_game() {
+    let mut sim = Sim::new();
+    let bottom = sim.alien_positions().map(|(row, _)| row).max().unwrap() as i16;
+    for alien in sim.game.aliens.aliens.iter_mut() {
+        alien.pos.row += (HEIGHT - INVASION_HEIGHT) as i16 - 1 - bottom;
+    }
+    sim.game.move_aliens();
+    assert_eq!(sim.game.status(), Status::Normal);
+    for alien in sim.game.aliens.aliens.iter_mut() {
+        alien.pos.row += 1;
+    }
+    sim.game.move_aliens();
+    assert_eq!(sim.game.status(), Status::Over);
+}
+
+// The bomb lands in the same update, but the game is already over
+#[test]
+fn a_bomb_cannot_hit_after_the_formation_lands() {
+    let mut sim = Sim::blank();
+    let (row, col) = sim.player_pos();
+    sim.place_alien(HEIGHT - INVASION_HEIGHT, 10);
+    sim.game.alien_shots.set_capacity(1);
+    sim.game.alien_shots.fire(pos(row - 1, col), Dir::S, ShotKind::Plunger);
+    sim.game.alien_move_countdown = 0;
+    sim.run(1);
+    assert_eq!((sim.game.status(), sim.game.lives()), (Status::Over, 0));
+}
+
+#[test]
+fn clearing_the_formation_starts_a_harder_wave() {
+    let mut sim = Sim::blank();
+    let (row, col) = sim.player_pos();
+    sim.place_alien(row - 4, col).hold_alien_fire();
+    sim.play(&[FIRE, Input::Updates(3)]);
+    assert_eq!(sim.game.status(), Status::NextWave);
+    assert_eq!(sim.game.wave(), 2);
+
+    sim.run(WAVE_PAUSE);
+    assert_eq!(sim.game.status(), Status::NextWave);
+    sim.run(1);
+    assert_eq!(sim.game.status(), Status::Normal);
+    assert_eq!(sim.game.score(), AlienKind::Octopus.points());
+    assert!(sim.game.alien_move_frequency < Level::<WIDTH, HEIGHT>::for_wave(&LEVELS, 1).alien_move_frequency);
+
+    let second_level = Level::<WIDTH, HEIGHT>::for_wave(&LEVELS, 2);
+    let dropped: Vec<Position> = second_level.aliens.live().map(|a| a.pos.neighbor(Dir::S)).collect();
+    assert_eq!(sim.game.aliens.live().map(|a| a.pos).collect::<Vec<_>>(), dropped);
+    assert_eq!(sim.game.cells, second_level.cells);
+}
+
+// Whichever level a wave uses, its formation starts no higher than the last
+#[test]
+fn each_wave_starts_the_formation_lower() {
+    let mut game = Game::new();
+    let mut top = 0;
+    for wave in 1..=LEVELS.len() * 3 {
+        game.wave = wave;
+        game.set_up_game();
+        let start = game.aliens.bounds().unwrap().0.row;
+        assert!(start >= top, "wave {} starts on row {}, above row {}", wave, start, top);
+        assert!(start > top || wave > MAX_WAVE_DROP, "wave {} starts no lower than the last", wave);
+        top = start;
+    }
+}
+
+#[test]
+fn hiding_from_the_formation_does_not_stop_the_shooting() {
+    let mut sim = Sim::blank();
+    sim.place_alien(3, 2).hold_formation();
+    sim.run(1);
+    assert_eq!(sim.shot_counts(), (0, 1));
+}
+
+#[test]
+fn only_lowest_live_alien_in_column_fires() {
+    let mut sim = Sim::blank();
+    let (_, col) = sim.player_pos();
+    sim.place_alien(3, col).place_alien(6, col).place_alien(8, col);
+    sim.kill_aliens(|(row, _)| row == 8);
+    sim.run(1);
+    assert_eq!(sim.active_shot_positions().collect::<Vec<_>>(), vec![(7, col)]);
+}
+
+#[test]
+fn dead_aliens_stay_put() {
+    let mut sim = Sim::blank();
+    sim.place_alien(3, 10).place_alien(3, 20);
+    sim.kill_aliens(|(_, col)| col == 20);
+    sim.game.move_aliens();
+    assert_eq!(sim.alien_positions().collect::<Vec<_>>(), vec![(3, 11)]);
+    assert!(sim.game.aliens.aliens.iter().any(|a| !a.alive && a.pos == pos(3, 20)));
+}
+
+#[test]
+fn dead_aliens_cannot_end_the_game() {
+    let mut sim = Sim::blank();
+    sim.place_alien(3, 10).place_alien(HEIGHT - INVASION_HEIGHT, 20);
+    sim.kill_aliens(|(_, col)| col == 20);
+    sim.game.move_aliens();
+    assert_eq!(sim.game.status(), Status::Normal);
+}
+
+#[test]
+fn level_table_repeats_with_harder_settings() {
+    let mut game = Game::new();
+    let first = Level::<WIDTH, HEIGHT>::for_wave(&LEVELS, 1);
+    game.wave = LEVELS.len() + 1;
+    game.set_up_game();
+    assert_eq!(game.alien_move_frequency, first.alien_move_frequency - 1);
+    assert_eq!(game.alien_shots.capacity(), first.alien_shots + 1);
+    let dropped: Vec<Position> = first.aliens.live().map(|a| Position {row: a.pos.row + LEVELS.len() as i16, col: a.pos.col}).collect();
+    assert_eq!(game.aliens.live().map(|a| a.pos).collect::<Vec<_>>(), dropped);
+}
+
+#[test]
+fn cleared_edge_columns_let_the_formation_travel_further() {
+    let mut sim = Sim::new();
+    sim.kill_aliens(|(_, col)| col > 40);
+    let start: Vec<(usize, usize)> = sim.alien_positions().collect();
+    let rightmost = start.iter().map(|(_, col)| *col).max().unwrap();
+    assert!(rightmost <= 40);
+    for _ in 0..WIDTH - 1 - rightmost {
+        sim.game.move_aliens();
+    }
+    assert_eq!(sim.game.aliens.dir, Dir::E);
+    assert_eq!(sim.alien_positions().map(|(row, _)| row).max(), start.iter().map(|(row, _)| *row).max());
+    assert_eq!(sim.alien_positions().map(|(_, col)| col).max(), Some(WIDTH - 1));
+    sim.game.move_aliens();
+    assert_eq!(sim.game.aliens.dir, Dir::W);
+}
+
+#[test]
+fn alien_kinds_score_differently() {
+    for kind in [AlienKind::Squid, AlienKind::Crab, AlienKind::Octopus].iter() {
+        let mut sim = Sim::blank();
+        let (row, col) = sim.player_pos();
+        sim.place_alien_kind(row - 4, col, *kind).place_alien(1, 2).hold_alien_fire().hold_formation();
+        for _ in 0..kind.hits() {
+            sim.play(&[FIRE, Input::Updates(3)]);
+        }
+        assert_eq!(sim.live_aliens(), 1);
+        assert_eq!(sim.game.score(), kind.points());
+    }
+    assert_eq!(AlienKind::Squid.points(), 30);
+    assert_eq!(AlienKind::Crab.points(), 20);
+    assert_eq!(AlienKind::Octopus.points(), 10);
+}
+
+#[test]
+fn crabs_take_two_hits() {
+    let mut sim = Sim::blank();
+    let (row, col) = sim.player_pos();
+    sim.place_alien_kind(row - 4, col, AlienKind::Crab).place_alien(1, 2).hold_alien_fire().hold_formation();
+    sim.play(&[FIRE, Input::Updates(3)]);
+    assert_eq!(sim.live_aliens(), 2);
+    assert_eq!(sim.game.score(), 0);
+    assert!(sim.game.alien_at(pos(row - 4, col)).unwrap().1.damaged());
+    sim.play(&[FIRE, Input::Updates(3)]);
+    assert_eq!(sim.live_aliens(), 1);
+    assert_eq!(sim.game.score(), AlienKind::Crab.points());
+}
+
+#[test]
+fn squids_fire_faster() {
+    let mut squid = Sim::blank();
+    let (row, col) = squid.player_pos();
+    squid.place_alien_kind(row - 10, col, AlienKind::Squid);
+    squid.game.alien_shots.set_capacity(2);
+    squid.run(2);
+    assert_eq!(squid.shot_counts(), (0, 2));
+
+    let mut octopus = Sim::blank();
+    octopus.place_alien_kind(row - 10, col, AlienKind::Octopus);
+    octopus.game.alien_shots.set_capacity(2);
+    octopus.run(3);
+    assert_eq!(octopus.shot_counts(), (0, 1));
+}
+
+#[test]
+fn ufo_crosses_the_top_row_on_a_timer() {
+    let mut sim = Sim::blank();
+    sim.place_alien(5, 2).hold_alien_fire().hold_formation();
+    assert_eq!(sim.run_until(UFO_INTERVAL * 2, |g| g.ufo().active), Some(UFO_INTERVAL + 1));
+    // No shots fired yet, so it enters from the left
+    assert_eq!(sim.game.ufo().icon_at(pos(0, 0)), Some('<'));
+    assert_eq!(sim.game.ufo().icon_at(pos(0, 1)), Some('O'));
+    assert_eq!(sim.game.ufo().icon_at(pos(0, 2)), Some('>'));
+    assert_eq!(sim.game.ufo().icon_at(pos(1, 1)), None);
+    let crossing = sim.run_until(WIDTH * 2, |g| !g.ufo().active);
+    assert_eq!(crossing, Some(WIDTH - UFO_WIDTH as usize + 1));
+    assert_eq!(sim.game.ufo().countdown, UFO_INTERVAL);
+}
+
+#[test]
+fn ufo_enters_from_the_right_after_an_odd_shot_count() {
+    let mut sim = Sim::blank();
+    sim.place_alien(5, 2).hold_alien_fire().hold_formation();
+    sim.play(&[FIRE, Input::Updates(1)]);
+    sim.run_until(UFO_INTERVAL * 2, |g| g.ufo().active);
+    assert_eq!(sim.game.ufo().icon_at(pos(0, WIDTH - 1)), Some('>'));
+    assert_eq!(sim.game.ufo().dir, Dir::W);
+}
+
+#[test]
+fn shooting_the_ufo_scores_a_bonus_and_shows_it() {
+    let mut sim = Sim::blank();
+    let (row, col) = sim.player_pos();
+    sim.place_alien(5, 2).hold_alien_fire().hold_formation();
+    // Time the shot so it reaches the top row as the ship passes overhead
+    sim.game.ufo.launch::<WIDTH>(Dir::E);
+    // The shot ends its first update on row - 2 and climbs a row per update
+    let climb = row - 2;
+    sim.run(col - 2 - climb);
+    sim.play(&[FIRE]);
+    sim.run(climb + 1);
+    assert!(!sim.game.ufo().active);
+    let points = UFO_POINTS[1];
+    assert_eq!(sim.game.score(), points);
+    assert_eq!(sim.shot_counts(), (0, 0));
+    let popup = sim.game.ufo_popup().unwrap();
+    assert_eq!(popup.points(), points);
+    assert!((popup.col() as i16 - col as i16).abs() <= 1);
+    sim.run(UFO_POPUP_TIME + 1);
+    assert_eq!(sim.game.ufo_popup(), None);
+}
+
+#[test]
+fn new_game_waits_on_the_title_screen() {
+    let mut game = Game::new();
+    assert_eq!(game.status(), Status::Title);
+    let before = game;
+    game.key(DecodedKey::RawKey(KeyCode::ArrowLeft));
+    game.key(DecodedKey::Unicode('p'));
+    game.update();
+    // Only the wait for the attract-mode demo moves on
+    assert_eq!(Game {attract_countdown: before.attract_countdown, ..game}, before);
+    game.key(DecodedKey::RawKey(KeyCode::Enter));
+    assert_eq!(game.status(), Status::Normal);
+}
+
+#[test]
+fn options_screen_picks_presets_and_rebinds_keys() {
+    let mut game = Game::new();
+    game.key(DecodedKey::Unicode('o'));
+    assert_eq!(game.status(), Status::Options);
+    game.key(DecodedKey::Unicode('w'));
+    assert_eq!(*game.bindings(), input::KeyBindings::preset(input::Preset::Wasd));
+    game.key(DecodedKey::Unicode('3'));
+    assert_eq!(game.rebinding(), Some(input::Action::Fire));
+    game.key(DecodedKey::RawKey(KeyCode::K));
+    assert_eq!(game.rebinding(), None);
+    game.key(DecodedKey::Unicode('1'));
+    game.key(DecodedKey::Unicode('\u{1b}'));
+    assert!(game.bindings().is(DecodedKey::Unicode('a'), input::Action::MoveLeft));
+    game.key(DecodedKey::Unicode('0'));
+    assert_eq!(game.rebinding(), Some(input::Action::Options));
+    game.key(DecodedKey::Unicode('\u{1b}'));
+    game.key(DecodedKey::Unicode('9'));
+    game.key(DecodedKey::Unicode('t'));
+    game.key(DecodedKey::Unicode('\n'));
+    assert_eq!(game.status(), Status::Title);
+    game.key(DecodedKey::Unicode('2'));
+    assert_eq!(game.status(), Status::Title);
+    game.key(DecodedKey::Unicode('o'));
+    assert_eq!(game.status(), Status::Options);
+    game.key(DecodedKey::Unicode('\n'));
+
+    let mut sim = Sim {game, updates: 0};
+    sim.play(&[Input::Key(DecodedKey::Unicode('T')), Input::Updates(1)]);
+    assert_eq!(sim.game.players, 2);
+    let (row, col) = sim.player_pos();
+    sim.play(&[Input::Key(DecodedKey::Unicode('A')), Input::Updates(1), FIRE, Input::Updates(1)]);
+    assert_eq!(sim.player_pos(), (row, col - 1));
+    assert_eq!(sim.game.player.shots_fired, 0);
+    sim.play(&[Input::Key(DecodedKey::Unicode('k')), Input::Updates(1)]);
+    assert_eq!(sim.game.player.shots_fired, 1);
+}
+
+#[test]
+fn numpad_preset_moves_with_either_numlock_state() {
+    let mut sim = Sim::new();
+    sim.game.bindings = input::KeyBindings::preset(input::Preset::Numpad);
+    let (row, col) = sim.player_pos();
+    sim.play(&[Input::Key(DecodedKey::Unicode('4')), Input::Updates(1), LEFT, Input::Updates(1)]);
+    assert_eq!(sim.player_pos(), (row, col - 2));
+    sim.play(&[Input::Key(DecodedKey::RawKey(KeyCode::Numpad6)), Input::Updates(1)]);
+    assert_eq!(sim.player_pos(), (row, col - 1));
+}
+
+#[test]
+fn pause_freezes_everything_until_resumed() {
+    let mut sim = Sim::new();
+    sim.play(&[FIRE, Input::Updates(3), PAUSE]);
+    assert_eq!(sim.game.status(), Status::Paused);
+    let before = sim.game;
+    sim.play(&[LEFT, FIRE, Input::Updates(50)]);
+    assert!(sim.game.countdown_complete());
+    assert_eq!(sim.game, before);
+    sim.play(&[Input::Key(DecodedKey::RawKey(KeyCode::Escape))]);
+    assert_eq!(sim.game.status(), Status::Normal);
+    sim.run(1);
+    let mut resumed = before;
+    resumed.resume();
+    assert_ne!(sim.game, resumed);
+}
+
+#[test]
+fn pausing_while_dying_resumes_the_respawn() {
+    let mut sim = Sim::new();
+    sim.game.player_hit();
+    sim.run(3);
+    let countdown = sim.game.respawn_countdown();
+    sim.play(&[PAUSE, Input::Updates(RESPAWN_TIME * 2)]);
+    assert_eq!(sim.game.respawn_countdown(), countdown);
+    sim.play(&[PAUSE]);
+    assert_eq!(sim.game.status(), Status::Dying);
+    sim.run_until(RESPAWN_TIME, |g| g.status() == Status::Normal).unwrap();
+}
+
+#[test]
+fn pausing_between_waves_resumes_the_wave_countdown() {
+    let mut sim = Sim::blank();
+    sim.place_alien(1, 2).hold_alien_fire();
+    sim.kill_aliens(|_| true);
+    sim.run(1);
+    assert_eq!(sim.game.status(), Status::NextWave);
+    let countdown = sim.game.wave_countdown;
+    sim.play(&[PAUSE, Input::Updates(WAVE_PAUSE * 2), PAUSE]);
+    assert_eq!(sim.game.status(), Status::NextWave);
+    assert_eq!(sim.game.wave_countdown, countdown);
+    sim.run_until(WAVE_PAUSE + 2, |g| g.status() == Status::Normal).unwrap();
+    assert_eq!(sim.game.wave(), 2);
+}
+
+#[test]
+fn firing_and_kills_ask_for_sounds() {
+    let mut sim = Sim::blank();
+    let (row, col) = sim.player_pos();
+    sim.place_alien(row - 3, col).hold_alien_fire().hold_formation();
+    sim.game.take_sounds();
+    sim.play(&[FIRE, Input::Updates(1)]);
+    assert!(sim.game.take_sounds().iter().any(|s| *s == Sound::Shot));
+    sim.run(2);
+    assert!(sim.game.take_sounds().iter().any(|s| *s == Sound::AlienExplosion));
+}
+
+#[test]
+fn getting_hit_asks_for_the_death_sound() {
+    let mut sim = Sim::new();
+    sim.game.take_sounds();
+    sim.game.player_hit();
+    assert_eq!(sim.game.take_sounds().iter().copied().collect::<Vec<_>>(), vec![Sound::PlayerDeath]);
+}
+
+#[test]
+fn march_cycles_four_notes_and_speeds_up_as_aliens_die() {
+    let mut sim = Sim::new();
+    sim.hold_alien_fire().hold_formation();
+    sim.game.take_sounds();
+    let march_updates = |sim: &mut Sim, notes: usize| {
+        let mut heard = vec![];
+        for update in 0.. {
+            sim.run(1);
+            for sound in sim.game.take_sounds().iter() {
+                if let Sound::March(note) = sound {
+                    heard.push((update, *note));
+                }
+            }
+            if heard.len() == notes {
+                return heard
+            }
+        }
+        unreachable!()
+    };
+    let full = march_updates(&mut sim, 5);
+    assert_eq!(full.iter().map(|(_, note)| *note).collect::<Vec<_>>(), vec![0, 1, 2, 3, 0]);
+    assert_eq!(full[2].0 - full[1].0, MARCH_SLOWEST + 1);
+    let (row, col) = sim.alien_positions().next().unwrap();
+    sim.kill_aliens(|p| p != (row, col));
+    march_updates(&mut sim, 1);
+    let last = march_updates(&mut sim, 2);
+    assert_eq!(last[1].0 - last[0].0, MARCH_FASTEST + 1);
+}
+
+#[test]
+fn mute_key_silences_the_game() {
+    let mut sim = Sim::new();
+    sim.game.take_sounds();
+    sim.play(&[Input::Key(DecodedKey::Unicode('m')), FIRE, Input::Updates(MARCH_SLOWEST * 2)]);
+    assert!(sim.game.muted());
+    assert_eq!(sim.game.take_sounds().iter().count(), 0);
+    sim.play(&[Input::Key(DecodedKey::RawKey(KeyCode::M)), FIRE, Input::Updates(1)]);
+    assert!(!sim.game.muted());
+    assert!(sim.game.take_sounds().iter().any(|s| *s == Sound::Shot));
+}
+
+#[test]
+fn a_good_enough_score_asks_for_initials() {
+    let mut sim = Sim::new();
+    sim.game.score = 1500;
+    sim.lose_last_life();
+    assert_eq!(sim.game.status(), Status::EnteringInitials);
+    // Too few letters can't be entered; extras and digits are ignored; mute is just an M
+    sim.type_text("m\n");
+    assert_eq!(sim.game.status(), Status::EnteringInitials);
+    sim.type_text("x\u{8}j7rzq\n");
+    assert_eq!(sim.game.status(), Status::Over);
+    assert!(!sim.game.muted());
+    let entry = *sim.game.high_scores().iter().next().unwrap();
+    assert_eq!((entry.initials(), entry.score()), ("MJR", 1500));
+    assert_eq!(sim.game.take_high_scores_to_save(), Some(*sim.game.high_scores()));
+    assert_eq!(sim.game.take_high_scores_to_save(), None);
+    sim.play(&[RESTART]);
+    assert_eq!(sim.game.status(), Status::Normal);
+    assert_eq!(sim.game.high_scores().len(), 1);
+}
+
+#[test]
+fn a_letter_bound_to_confirm_can_still_be_an_initial() {
+    let mut sim = Sim::new();
+    sim.game.bindings.bind(input::Action::Confirm, DecodedKey::Unicode('x'));
+    sim.game.score = 1500;
+    sim.lose_last_life();
+    sim.type_text("xax");
+    assert_eq!(sim.game.status(), Status::EnteringInitials);
+    sim.type_text("x");
+    assert_eq!(sim.game.status(), Status::Over);
+    assert_eq!(sim.game.high_scores().iter().next().unwrap().initials(), "XAX");
+}
+
+#[test]
+fn a_score_off_the_table_just_ends_the_game() {
+    let mut sim = Sim::new();
+    let mut table = high_scores::HighScores::new();
+    for _ in 0..high_scores::MAX_HIGH_SCORES {
+        table.insert(*b"ACE", 5000);
+    }
+    sim.game.set_high_scores(table);
+    sim.game.score = 5000;
+    sim.lose_last_life();
+    assert_eq!(sim.game.status(), Status::Over);
+    assert_eq!(sim.game.take_high_scores_to_save(), None);
+}
+
+#[test]
+fn scoreless_games_stay_off_the_table() {
+    let mut sim = Sim::new();
+    sim.lose_last_life();
+    assert_eq!(sim.game.status(), Status::Over);
+}
+
+#[test]
+fn each_side_fires_from_its_own_pool() {
+    let mut sim = Sim::blank();
+    let (_, col) = sim.player_pos();
+    sim.place_alien(3, col).hold_formation();
+    sim.game.alien_shots.set_capacity(shots::SHOT_POOL_SIZE);
+    sim.game.aliens.fire_countdown = 0;
+    for _ in 0..shots::SHOT_POOL_SIZE {
+        sim.game.aliens.fire_countdown = 0;
+        sim.game.alien_shoot();
+    }
+    assert_eq!(sim.shot_counts(), (0, shots::SHOT_POOL_SIZE));
+    for _ in 0..shots::PLAYER_SHOTS + 1 {
+        sim.game.player_shoot();
+    }
+    assert_eq!(sim.shot_counts(), (shots::PLAYER_SHOTS, shots::SHOT_POOL_SIZE));
+}
+
+#[test]
+fn lasers_outrun_bombs() {
+    let mut sim = Sim::blank();
+    sim.place_alien(3, 10).hold_formation();
+    sim.play(&[FIRE]);
+    sim.run(1);
+    sim.hold_alien_fire().run(4);
+    let (row, col) = sim.player_pos();
+    assert_eq!(sim.active_shot_positions().collect::<Vec<_>>(), vec![(row - 6, col), (6, 10)]);
+}
+
+#[test]
+fn a_laser_cancels_a_bomb() {
+    let mut sim = Sim::blank();
+    let (row, col) = sim.player_pos();
+    // Far enough apart that the shots pass each other between cells
+    sim.place_alien(row - 9, col).place_alien(1, col).hold_formation();
+    sim.run(1);
+    sim.hold_alien_fire();
+    sim.play(&[FIRE, Input::Updates(4)]);
+    assert_eq!(sim.shot_counts(), (1, 1));
+    sim.run(1);
+    assert_eq!(sim.shot_counts(), (0, 0));
+    assert_eq!(sim.live_aliens(), 2);
+    assert_eq!(sim.game.status(), Status::Normal);
+}
+
+#[test]
+fn occupancy_tracks_every_alien_and_shot() {
+    let mut sim = Sim::new();
+    for update in 0..300 {
+        sim.play(&[if update % 40 < 20 {LEFT} else {RIGHT}, FIRE, Input::Updates(1)]);
+        for p in sim.game.cell_pos_iter() {
+            let alien = sim.game.aliens.live().find(|alien| alien.pos == p);
+            assert_eq!(sim.game.alien_at(p).map(|(_, alien)| alien), alien);
+            let shot = sim.game.player_shots.active().chain(sim.game.alien_shots.active()).find(|shot| shot.pos == p);
+            assert_eq!(sim.game.shot_at(p), shot);
+        }
+    }
+    assert!(sim.game.score() > 0);
+}
+
+#[test]
+fn entity_at_prefers_what_the_screen_shows() {
+    let mut sim = Sim::blank();
+    sim.place_alien(5, 10).hold_formation().hold_alien_fire();
+    assert!(matches!(sim.game.entity_at(pos(5, 10)), Some(EntityRef::Alien(_, alien)) if alien.pos == pos(5, 10)));
+    assert!(matches!(sim.game.entity_at(sim.game.player.pos), Some(EntityRef::Player(_))));
+    assert_eq!(sim.game.entity_at(pos(6, 10)), None);
+    sim.play(&[FIRE, Input::Updates(1)]);
+    let (row, col) = sim.player_pos();
+    assert!(matches!(sim.game.entity_at(pos(row - 2, col)), Some(EntityRef::Shot(shot)) if shot.kind() == ShotKind::Laser));
+}
+
+#[test]
+fn starting_a_game_is_reported_once() {
+    let mut game = Game::new();
+    assert!(!game.take_game_started());
+    game.key(DecodedKey::Unicode('\n'));
+    assert!(game.take_game_started());
+    assert!(!game.take_game_started());
+    let mut sim = Sim {game, updates: 0};
+    sim.lose_last_life();
+    sim.play(&[RESTART]);
+    assert!(sim.game.take_game_started());
+}
+
+// The kernel starts a new recording, with a new seed, as each game starts
+#[test]
+fn recordings_of_a_started_game_replay_it() {
+    let mut game = Game::new();
+    game.bindings = input::KeyBindings::preset(input::Preset::Numpad);
+    game.key(DecodedKey::Unicode('2'));
+    assert!(game.take_game_started());
+    let mut recording = replay::Recording::new_game(0xFACE, &mut game);
+    let script = [(5, DecodedKey::Unicode('4')), (9, DecodedKey::Unicode('8')), (30, DecodedKey::Unicode('6'))];
+    for tick in 0..120 {
+        for (_, key) in script.iter().filter(|(at, _)| *at == tick) {
+            recording.record(*key);
+            game.key(*key);
+        }
+        game.tick();
+        recording.tick();
+    }
+    let mut log = String::new();
+    recording.write_to(&mut log).unwrap();
+    assert_eq!(Sim::replay(&log).game, game);
+}
+
+#[test]
+fn exported_logs_replay_to_the_same_game() {
+    let mut high_scores = HighScores::new();
+    high_scores.insert(*b"ACE", 40);
+    let mut recording = replay::Recording::new(77, high_scores);
+    let mut game = recording.start_game();
+    let script = [(3, DecodedKey::Unicode('\n')), (10, DecodedKey::RawKey(KeyCode::ArrowLeft)),
+                  (10, DecodedKey::Unicode(' ')), (14, DecodedKey::Unicode(' ')), (40, DecodedKey::RawKey(KeyCode::ArrowRight)),
+                  (41, DecodedKey::Unicode('p')), (60, DecodedKey::Unicode('p'))];
+    for tick in 0..200 {
+        for (_, key) in script.iter().filter(|(at, _)| *at == tick) {
+            recording.record(*key);
+            game.key(*key);
+        }
+        game.tick();
+        recording.tick();
+    }
+    let mut log = String::new();
+    recording.write_to(&mut log).unwrap();
+    let sim = Sim::replay(&log);
+    assert_eq!(sim.game, game);
+    assert_ne!(sim.game, recording.start_game());
+}
+
+#[test]
+fn held_keys_move_a_column_every_update() {
+    let mut sim = Sim::blank();
+    sim.place_alien(1, 2).hold_formation().hold_alien_fire();
+    let (row, col) = sim.player_pos();
+    sim.play(&[Input::Hold(KeyCode::ArrowLeft), LEFT, Input::Updates(5)]);
+    assert_eq!(sim.player_pos(), (row, col - 5));
+    // Keyboard repeats of a key already down change nothing
+    sim.play(&[Input::Hold(KeyCode::ArrowLeft), Input::Hold(KeyCode::ArrowRight), Input::Updates(3)]);
+    assert_eq!(sim.player_pos(), (row, col - 5));
+    sim.play(&[Input::Release(KeyCode::ArrowLeft), Input::Updates(3), Input::Release(KeyCode::ArrowRight), Input::Updates(3)]);
+    assert_eq!(sim.player_pos(), (row, col - 2));
+}
+
+#[test]
+fn firing_does_not_interrupt_held_movement() {
+    let mut sim = Sim::blank();
+    sim.place_alien(1, 2).hold_formation().hold_alien_fire();
+    let (row, col) = sim.player_pos();
+    sim.play(&[Input::Hold(KeyCode::ArrowRight), Input::Updates(2), Input::Hold(KeyCode::Spacebar), FIRE, Input::Updates(2)]);
+    assert_eq!(sim.player_pos(), (row, col + 4));
+    assert_eq!(sim.shot_counts().0, 1);
+    sim.play(&[Input::Hold(KeyCode::W), Input::Updates(1)]);
+    assert_eq!(sim.player_pos(), (row, col + 5));
+}
+
+#[test]
+fn two_players_take_turns_on_their_own_boards() {
+    let mut sim = Sim::two_players();
+    assert_eq!((sim.game.players(), sim.game.current_player()), (2, 0));
+    let full = sim.live_aliens();
+    sim.kill_aliens(|(row, _)| row == 3);
+    sim.game.add_score(120);
+    let player_one_aliens = sim.live_aliens();
+    sim.lose_turn();
+    assert_eq!(sim.game.status(), Status::NextPlayer);
+    assert_eq!((sim.game.current_player(), sim.game.score(), sim.game.lives()), (1, 0, STARTING_LIVES));
+    assert_eq!(sim.live_aliens(), full);
+    assert_eq!(sim.game.player_score(0), 120);
+    assert_eq!(sim.run_until(TURN_PAUSE + 2, |game| game.status() == Status::Normal), Some(TURN_PAUSE + 1));
+    sim.game.add_score(30);
+    sim.lose_turn();
+    assert_eq!((sim.game.current_player(), sim.game.score(), sim.game.lives()), (0, 120, STARTING_LIVES - 1));
+    assert_eq!(sim.live_aliens(), player_one_aliens);
+    assert_eq!(sim.game.player_score(1), 30);
+}
+
+#[test]
+fn the_last_player_with_lives_plays_on_alone() {
+    let mut sim = Sim::two_players();
+    sim.game.add_score(1500);
+    sim.game.lives = 1;
+    sim.lose_turn();
+    assert_eq!(sim.game.status(), Status::EnteringInitials);
+    sim.type_text("one\n");
+    assert_eq!((sim.game.status(), sim.game.current_player()), (Status::NextPlayer, 1));
+    sim.run(TURN_PAUSE + 1);
+    // Player 1 is out, so player 2 keeps the board after a death
+    sim.lose_turn();
+    assert_eq!((sim.game.status(), sim.game.current_player()), (Status::Normal, 1));
+    sim.lose_last_life();
+    assert_eq!(sim.game.status(), Status::Over);
+    assert_eq!(sim.game.player_score(0), 1500);
+    sim.play(&[RESTART]);
+    assert_eq!((sim.game.players(), sim.game.current_player(), sim.game.score()), (2, 0, 0));
+}
+
+#[test]
+fn an_invaded_player_is_out_and_scores_once() {
+    let mut sim = Sim::two_players();
+    sim.game.add_score(1500);
+    for alien in sim.game.aliens.aliens.iter_mut() {
+        alien.pos.row += HEIGHT as i16 / 2;
+    }
+    sim.game.move_aliens();
+    assert_eq!((sim.game.status(), sim.game.lives()), (Status::EnteringInitials, 0));
+    sim.type_text("one\n");
+    assert_eq!((sim.game.status(), sim.game.current_player()), (Status::NextPlayer, 1));
+    sim.run(TURN_PAUSE + 1);
+    sim.lose_turn();
+    assert_eq!((sim.game.status(), sim.game.current_player()), (Status::Normal, 1));
+    sim.lose_last_life();
+    assert_eq!(sim.game.status(), Status::Over);
+    assert_eq!(sim.game.high_scores().len(), 1);
+}
+
+#[test]
+fn the_start_key_begins_a_one_player_game() {
+    let mut sim = Sim::new();
+    assert_eq!(sim.game.players(), 1);
+    sim.lose_turn();
+    assert_eq!((sim.game.status(), sim.game.current_player()), (Status::Normal, 0));
+}
+
+#[test]
+fn an_idle_title_screen_gives_way_to_the_demo() {
+    let mut game = Game::new();
+    for _ in 0..TITLE_TIME {
+        game.update();
+    }
+    game.key(DecodedKey::RawKey(KeyCode::M));
+    game.update();
+    assert!(!game.demo(), "a key puts the demo off");
+    for _ in 0..TITLE_TIME + 1 {
+        game.update();
+    }
+    assert!(game.demo());
+    assert_eq!(game.status(), Status::Normal);
+    game.key(DecodedKey::Unicode('\n'));
+    assert_eq!((game.status(), game.demo()), (Status::Title, false));
+}
+
+#[test]
+fn the_demo_plays_silently_and_leaves_no_score() {
+    let mut sim = Sim {game: Game::new(), updates: 0};
+    sim.run(TITLE_TIME + 1);
+    assert!(sim.game.demo());
+    sim.game.add_score(1500);
+    sim.run(20);
+    assert_eq!(sim.game.take_sounds().iter().count(), 0);
+    sim.lose_last_life();
+    assert_eq!((sim.game.status(), sim.game.demo()), (Status::Title, false));
+    assert!(sim.game.high_scores().is_empty());
+}
+
+#[test]
+fn the_demo_ends_when_its_time_is_up() {
+    let mut sim = Sim {game: Game::new(), updates: 0};
+    sim.run(TITLE_TIME + 1);
+    sim.game.attract_countdown = 1;
+    sim.run(1);
+    assert!(sim.game.demo());
+    sim.run(1);
+    assert_eq!((sim.game.status(), sim.game.demo()), (Status::Title, false));
+}
+
+const SMALL_LEVEL: &str = "speed: 2
+shots: 1
+---
+|..................|
+|...@.@.@.@.@.@....|
+|...X.X.X.X.X.X....|
+|..................|
+|..................|
+|..................|
+|..................|
+|..................|
+|..................|
+|..##....##....##..|
+|..................|
+|.........^........|";
+
+#[test]
+fn bad_levels_are_refused_when_the_game_is_built() {
+    const BAD_LEVEL: &str = "speed: 2\nshots: 1\n---\n..x..\n..^..";
+    let error = SpaceInvadersGame::<20, 12>::with_levels(&[SMALL_LEVEL, BAD_LEVEL]).unwrap_err();
+    assert_eq!(error, level::LevelError {row: 4, col: 3, kind: level::LevelErrorKind::UnknownIcon('x')});
+    let error = SpaceInvadersGame::<20, 12>::with_levels(&[]).unwrap_err();
+    assert_eq!(error.kind, level::LevelErrorKind::NoLevels);
+    // The kernel's levels don't fit this board
+    assert!(SpaceInvadersGame::<20, 12>::with_levels(&LEVELS).is_err());
+}
+
+#[test]
+fn a_small_board_plays_within_its_own_bounds() {
+    let mut game = SpaceInvadersGame::<20, 12>::with_levels(&[SMALL_LEVEL]).unwrap();
+    assert_eq!(game.cell_pos_iter().count(), 20 * 12);
+    assert!(game.is_legal(pos(11, 19)) && !game.is_legal(pos(12, 0)) && !game.is_legal(pos(0, 20)));
+    game.key(DecodedKey::Unicode('\n'));
+    game.set_invincible(true);
+    game.spawn_ufo();
+    for _ in 0..2000 {
+        ai::play(&mut game);
+        game.update();
+        assert!(game.shots().all(|shot| game.is_legal(shot.pos())));
+        assert!(game.live_aliens().all(|alien| game.is_legal(alien.pos())));
+    }
+    assert!(game.score() > 0, "the bot never hit anything");
+}
@@ -0,0 +1,49 @@
+// Reads the keyboard directly instead of through the interrupt handler's
+// decoded keys, so key releases are seen too. IRQ 1 is masked at the PIC and
+// the controller is polled once a tick; the keyboard buffers what arrives in
+// between.
+
+use lazy_static::lazy_static;
+use pc_keyboard::{layouts, DecodedKey, HandleControl, KeyEvent, Keyboard, ScancodeSet1};
+use spin::Mutex;
+use x86_64::instructions::port::Port;
+
+const DATA: u16 = 0x60;
+const STATUS: u16 = 0x64;
+const OUTPUT_FULL: u8 = 0x01;
+const PIC1_DATA: u16 = 0x21;
+const KEYBOARD_IRQ: u8 = 1;
+// Bytes taken per poll, so a stuck controller can't hold up the tick
+const MAX_BYTES_PER_POLL: usize = 16;
+
+lazy_static! {
+    static ref KEYBOARD: Mutex<Keyboard<layouts::Us104Key, ScancodeSet1>> =
+        Mutex::new(Keyboard::new(layouts::Us104Key, ScancodeSet1, HandleControl::Ignore));
+}
+
+// Stops the keyboard interrupt from taking scancodes before the poll sees them
+pub fn take_over() {
+    let mut mask = Port::<u8>::new(PIC1_DATA);
+    unsafe {
+        let masked = mask.read() | 1 << KEYBOARD_IRQ;
+        mask.write(masked);
+    }
+}
+
+// Hands every key event waiting in the controller to on_event, along with
+// the key it types, if any
+pub fn poll<F: FnMut(&KeyEvent, Option<DecodedKey>)>(mut on_event: F) {
+    let mut keyboard = KEYBOARD.lock();
+    let mut status = Port::<u8>::new(STATUS);
+    let mut data = Port::<u8>::new(DATA);
+    for _ in 0..MAX_BYTES_PER_POLL {
+        if unsafe { status.read() } & OUTPUT_FULL == 0 {
+            break
+        }
+        let scancode = unsafe { data.read() };
+        if let Ok(Some(event)) = keyboard.add_byte(scancode) {
+            let typed = keyboard.process_keyevent(event.clone());
+            on_event(&event, typed);
+        }
+    }
+}
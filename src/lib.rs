@@ -1,82 +1,184 @@
 #![cfg_attr(not(test), no_std)]
 
-
-use pluggable_interrupt_os::vga_buffer::{BUFFER_WIDTH, BUFFER_HEIGHT, plot, plot_str, plot_num, clear_row, ColorCode, Color};
 pub mod game_core;
+pub mod platform;
+pub mod vga_view;
+#[cfg(target_os = "none")]
+mod ata;
+#[cfg(target_os = "none")]
+mod keyboard;
+#[cfg(target_os = "none")]
+mod serial;
+#[cfg(target_os = "none")]
+mod sound;
+#[cfg(target_os = "none")]
+mod vga_platform;
 
-use crate::game_core::{SpaceInvadersGame, Status, Cell, Position, Alien, Player, Shot};
+use crate::game_core::{SpaceInvadersGame, BOARD_WIDTH, BOARD_HEIGHT};
+#[cfg(target_os = "none")]
+use pc_keyboard::{DecodedKey, KeyCode, KeyEvent};
+#[cfg(target_os = "none")]
+use crate::game_core::high_scores::HighScores;
+#[cfg(target_os = "none")]
+use crate::game_core::console::Console;
+#[cfg(target_os = "none")]
+use crate::game_core::replay::{Recording, Replay};
+#[cfg(target_os = "none")]
+use crate::vga_platform::VgaPlatform;
+#[cfg(target_os = "none")]
+use crate::vga_view::Renderer;
 
-const GAME_HEIGHT: usize = BUFFER_HEIGHT - 2;
-const HEADER_SPACE: usize = BUFFER_HEIGHT - GAME_HEIGHT;
+pub type MainGame = SpaceInvadersGame<BOARD_WIDTH, BOARD_HEIGHT>;
 
+#[cfg(target_os = "none")]
+static SCORE_DISK: spin::Mutex<ata::AtaStorage> = spin::Mutex::new(ata::AtaStorage::new());
 
-pub type MainGame = SpaceInvadersGame;
+// Every key since the current game started, or since boot until the first
+// one does, ready to export or replay
+#[cfg(target_os = "none")]
+static RECORDING: spin::Mutex<Option<Recording>> = spin::Mutex::new(None);
 
-pub fn tick(game: &mut MainGame) {
-    if game.countdown_complete() {
-        game.update();
-        draw(game);
-    }
-}
+#[cfg(target_os = "none")]
+static REPLAY: spin::Mutex<Option<Replay>> = spin::Mutex::new(None);
 
-fn draw(game: &MainGame) {
-    draw_header(game);
-    draw_board(game);
-}
+#[cfg(target_os = "none")]
+static RENDERER: spin::Mutex<Renderer> = spin::Mutex::new(Renderer::new());
 
-fn draw_header(game: &MainGame) {
-    match game.status() {
-        Status::Normal => draw_normal_header(game),
-        Status::Over => draw_game_over_header(game)
-    }
+#[cfg(target_os = "none")]
+static CONSOLE: spin::Mutex<Console> = spin::Mutex::new(Console::new());
+
+// F9 sends the recording out over COM1
+#[cfg(target_os = "none")]
+const EXPORT_KEY: KeyCode = KeyCode::F9;
+// F10 replays the recording from its start; pressed again, it skips to the end
+#[cfg(target_os = "none")]
+const REPLAY_KEY: KeyCode = KeyCode::F10;
+
+// Seeds the game from the time stamp counter and picks up the high scores
+// saved on an earlier boot
+#[cfg(target_os = "none")]
+pub fn startup(game: &mut MainGame) {
+    let seed = unsafe { core::arch::x86_64::_rdtsc() };
+    let high_scores = HighScores::load_from(&mut *SCORE_DISK.lock());
+    game.seed(seed);
+    game.set_high_scores(high_scores);
+    *RECORDING.lock() = Some(Recording::new(seed, high_scores));
+    keyboard::take_over();
 }
 
-fn draw_normal_header(game: &MainGame) {
-    clear_row(1, Color::Black);
-    let header_color = ColorCode::new(Color::White, Color::Black);
-    let score_text = "Score:";
-    clear_row(0, Color::Black);
-    clear_row(1, Color::Black);
-    plot_str(score_text, 0, 0, header_color);
-    plot_num(game.score() as isize, score_text.len() + 1, 0, header_color);
+// Reads the keyboard, giving the game each key going down or up and then
+// the key it types
+#[cfg(target_os = "none")]
+fn poll_keyboard(game: &mut MainGame) {
+    keyboard::poll(|event, typed| {
+        key_event(game, event);
+        if let Some(typed) = typed {
+            key(game, typed);
+        }
+    });
 }
 
-fn draw_subheader(subheader: &str) {
-    plot_str(subheader, 0, 1, ColorCode::new(Color::LightRed, Color::Black));
+#[cfg(target_os = "none")]
+fn key_event(game: &mut MainGame, event: &KeyEvent) {
+    if REPLAY.lock().is_some() {
+        return
+    }
+    if let Some(recording) = RECORDING.lock().as_mut() {
+        recording.record_event(event);
+    }
+    game.key_event(event);
 }
 
-fn draw_game_over_header(game: &MainGame) {
-    draw_normal_header(game);
-    draw_subheader("Game over. Press S to restart.");
+// Keys are recorded on their way to the game, and ignored while a replay
+// is at the controls
+#[cfg(target_os = "none")]
+fn key(game: &mut MainGame, key: DecodedKey) {
+    match key {
+        DecodedKey::RawKey(EXPORT_KEY) => {
+            if let Some(recording) = RECORDING.lock().as_ref() {
+                serial::write_with(|out| recording.write_to(out));
+            }
+        }
+        DecodedKey::RawKey(REPLAY_KEY) => toggle_replay(game),
+        _ if REPLAY.lock().is_some() => {}
+        _ => {
+            if let Some(recording) = RECORDING.lock().as_mut() {
+                recording.record(key);
+            }
+            game.key(key);
+            if game.take_game_started() {
+                let seed = unsafe { core::arch::x86_64::_rdtsc() };
+                *RECORDING.lock() = Some(Recording::new_game(seed, game));
+            }
+        }
+    }
 }
 
-fn draw_board(game: &MainGame) {
-    for p in game.cell_pos_iter() {
-        let (row, col) = p.row_col();
-        let (c, color) = get_icon_color(game, p, &game.cell(p));
-        plot(c, col, row + HEADER_SPACE, color);
+// A replay ends in the state the recording left off in, so recording
+// carries on from there once it finishes
+#[cfg(target_os = "none")]
+fn toggle_replay(game: &mut MainGame) {
+    let mut replay = REPLAY.lock();
+    match replay.take() {
+        Some(mut running) => running.run(game),
+        None => {
+            if let Some(recording) = RECORDING.lock().as_ref() {
+                if let Ok(new_replay) = Replay::new(*recording) {
+                    *game = recording.start_game();
+                    *replay = Some(new_replay);
+                }
+            }
+        }
     }
 }
 
-fn get_icon_color(game: &MainGame, p: Position, cell: &Cell) -> (char, ColorCode) {
-    let (icon, foreground) =
-        if game.player_at(p) {
-            (match game.status() {
-                Status::Over => '*',
-                _ => Player::icon()
-            }, Color::Yellow)
-        } else {
-            if game.alien_at(p).is_some() {
-
-                ('@', Color::Green)
-            } else if game.shot_at(p) {
-                (Shot::icon(), Color::Red)
-            } else {
-                match cell {
-                    Cell::Empty => ('.', Color::White),
-                    Cell::Barrier => ('#', Color::Blue)
+#[cfg(target_os = "none")]
+pub fn tick(game: &mut MainGame) {
+    poll_keyboard(game);
+    let redraw = {
+        let mut replay = REPLAY.lock();
+        match replay.as_mut() {
+            Some(running) => {
+                let redraw = running.step(game);
+                // A game started in the replay is already on record
+                game.take_game_started();
+                if running.finished() {
+                    *replay = None;
+                }
+                redraw
+            }
+            None => {
+                let redraw = game.tick();
+                if let Some(recording) = RECORDING.lock().as_mut() {
+                    recording.tick();
                 }
+                redraw
             }
-        };
-    (icon, ColorCode::new(foreground, Color::Black))
+        }
+    };
+    // The console is polled first and on every tick: redraw is true on
+    // every tick while paused, which is when step needs it
+    let console = poll_console(game);
+    if redraw || console {
+        RENDERER.lock().draw(&mut VgaPlatform, game);
+    }
+    sound::update(&mut VgaPlatform, game);
+    if let Some(high_scores) = game.take_high_scores_to_save() {
+        high_scores.save_to(&mut *SCORE_DISK.lock());
+    }
+}
+
+// Runs whatever arrived on the serial line since the last tick; true if
+// anything did, since a command may have changed the board. Commands are not
+// part of the input recording, so a session that used them won't replay
+// the same.
+#[cfg(target_os = "none")]
+fn poll_console(game: &mut MainGame) -> bool {
+    let mut console = CONSOLE.lock();
+    let mut received = false;
+    while let Some(byte) = serial::read_byte() {
+        serial::write_with(|out| console.input(byte, game, out));
+        received = true;
+    }
+    received
 }
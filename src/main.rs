@@ -3,21 +3,17 @@
 
 use lazy_static::lazy_static;
 use spin::Mutex;
-use pc_keyboard::{DecodedKey, KeyCode};
 use pluggable_interrupt_os::HandlerTable;
-use pluggable_interrupt_os::vga_buffer::clear_screen;
-use crossbeam::atomic::AtomicCell;
-use pluggable_interrupt_os::println;
 use baremetal_game::MainGame;
 use baremetal_game::game_core::SpaceInvadersGame;
 
-pub mod game_core;
-
+// No keyboard handler: the game polls the keyboard itself each tick to see
+// keys being released as well as pressed
 #[no_mangle]
 pub extern "C" fn _start() -> ! {
     HandlerTable::new()
-        .keyboard(key)
         .timer(tick)
+        .startup(startup)
         .start()
 }
 
@@ -25,16 +21,10 @@ lazy_static! {
     static ref GAME: Mutex<MainGame> = Mutex::new(SpaceInvadersGame::new());
 }
 
-fn tick() {
-    baremetal_game::tick(&mut GAME.lock())
+fn startup() {
+    baremetal_game::startup(&mut GAME.lock())
 }
 
-fn key(key: DecodedKey) {
-    let mut game = GAME.lock();
-    game.key(key);
-}
-
-/*fn startup() {
-    clear_screen();
+fn tick() {
+    baremetal_game::tick(&mut GAME.lock())
 }
- */
\ No newline at end of file
@@ -0,0 +1,160 @@
+// What the game needs from the machine it runs on: a text screen, a clock
+// and a speaker. The kernel's implementation writes to VGA memory and the PC
+// speaker; MemoryPlatform keeps the screen in an array so host tests can
+// render frames and check them without QEMU.
+
+use core::fmt;
+
+pub const SCREEN_WIDTH: usize = 80;
+pub const SCREEN_HEIGHT: usize = 25;
+
+// The sixteen VGA text colours
+#[derive(Debug,Copy,Clone,Eq,PartialEq)]
+#[repr(u8)]
+pub enum Color {
+    Black,
+    Blue,
+    Green,
+    Cyan,
+    Red,
+    Magenta,
+    Brown,
+    LightGray,
+    DarkGray,
+    LightBlue,
+    LightGreen,
+    LightCyan,
+    LightRed,
+    Pink,
+    Yellow,
+    White
+}
+
+pub trait Platform {
+    // Writes a character in the given colour on black
+    fn plot(&mut self, c: char, col: usize, row: usize, color: Color);
+
+    fn clear_row(&mut self, row: usize);
+
+    // Called once a frame has been written, for platforms that buffer
+    fn present(&mut self) {}
+
+    // A monotonic clock in platform-defined units, for timing frames
+    fn now_ticks(&self) -> u64;
+
+    // Sounds the speaker at this pitch; 0 silences it
+    fn play_tone(&mut self, hz: u32);
+}
+
+// A screen in memory. The clock only moves when a test moves it.
+#[derive(Copy,Clone,Eq,PartialEq,Debug)]
+pub struct MemoryPlatform {
+    cells: [[(char, Color); SCREEN_WIDTH]; SCREEN_HEIGHT],
+    pending: usize, // cells written since the last present
+    plotted: usize, // cells written for the last frame presented
+    frames: usize,
+    pub ticks: u64,
+    pub tone: u32
+}
+
+impl Default for MemoryPlatform {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MemoryPlatform {
+    pub const fn new() -> Self {
+        MemoryPlatform {cells: [[(' ', Color::Black); SCREEN_WIDTH]; SCREEN_HEIGHT], pending: 0, plotted: 0, frames: 0, ticks: 0, tone: 0}
+    }
+
+    pub fn char_at(&self, col: usize, row: usize) -> char {
+        self.cells[row][col].0
+    }
+
+    pub fn color_at(&self, col: usize, row: usize) -> Color {
+        self.cells[row][col].1
+    }
+
+    // A row's text with the trailing blanks dropped
+    pub fn row_text(&self, row: usize) -> impl Iterator<Item=char> + '_ {
+        let len = self.cells[row].iter().rposition(|(c, _)| *c != ' ').map_or(0, |last| last + 1);
+        self.cells[row][..len].iter().map(|(c, _)| *c)
+    }
+
+    // Cells written during the last frame presented
+    pub fn plotted(&self) -> usize {
+        self.plotted
+    }
+
+    pub fn frames(&self) -> usize {
+        self.frames
+    }
+
+    // The screen as text for snapshot tests: a line of characters per row,
+    // a line of dashes, then a line of colours per row with each cell's
+    // colour as a hex digit (Black 0 to White f). Rows stop after their last
+    // cell that isn't a black blank.
+    pub fn write_snapshot<W: fmt::Write + ?Sized>(&self, out: &mut W) -> fmt::Result {
+        let blank = (' ', Color::Black);
+        let lens = self.cells.iter().map(|row| row.iter().rposition(|cell| *cell != blank).map_or(0, |last| last + 1));
+        for (row, len) in self.cells.iter().zip(lens.clone()) {
+            for (c, _) in row[..len].iter() {
+                out.write_char(*c)?;
+            }
+            writeln!(out)?;
+        }
+        for _ in 0..SCREEN_WIDTH {
+            out.write_char('-')?;
+        }
+        writeln!(out)?;
+        for (row, len) in self.cells.iter().zip(lens) {
+            for (_, color) in row[..len].iter() {
+                write!(out, "{:x}", *color as u8)?;
+            }
+            writeln!(out)?;
+        }
+        Ok(())
+    }
+}
+
+impl Platform for MemoryPlatform {
+    fn plot(&mut self, c: char, col: usize, row: usize, color: Color) {
+        if row < SCREEN_HEIGHT && col < SCREEN_WIDTH {
+            self.cells[row][col] = (c, color);
+            self.pending += 1;
+        }
+    }
+
+    fn clear_row(&mut self, row: usize) {
+        if row < SCREEN_HEIGHT {
+            self.cells[row] = [(' ', Color::Black); SCREEN_WIDTH];
+        }
+    }
+
+    fn present(&mut self) {
+        self.plotted = core::mem::take(&mut self.pending);
+        self.frames += 1;
+    }
+
+    fn now_ticks(&self) -> u64 {
+        self.ticks
+    }
+
+    fn play_tone(&mut self, hz: u32) {
+        self.tone = hz;
+    }
+}
+
+// The screen's text, a line per row
+impl fmt::Display for MemoryPlatform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for row in 0..SCREEN_HEIGHT {
+            for c in self.row_text(row) {
+                fmt::Write::write_char(f, c)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
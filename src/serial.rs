@@ -0,0 +1,35 @@
+// COM1, for debugging from whatever is on the other end of the serial line
+// (QEMU's stdio with the run-args in Cargo.toml). Input is polled once a tick
+// rather than taken by interrupt.
+
+use core::fmt::Write;
+use lazy_static::lazy_static;
+use spin::Mutex;
+use uart_16550::SerialPort;
+use x86_64::instructions::port::Port;
+
+const COM1: u16 = 0x3F8;
+const LINE_STATUS: u16 = COM1 + 5;
+const DATA_READY: u8 = 0x01;
+
+lazy_static! {
+    static ref PORT: Mutex<SerialPort> = {
+        let mut port = unsafe { SerialPort::new(COM1) };
+        port.init();
+        Mutex::new(port)
+    };
+}
+
+// Hands the port to f; output is best effort, so write errors are dropped
+pub fn write_with<F: FnOnce(&mut dyn Write) -> core::fmt::Result>(f: F) {
+    let _ = f(&mut *PORT.lock());
+}
+
+// The next byte received, if one is waiting
+pub fn read_byte() -> Option<u8> {
+    let port = PORT.lock(); // initialised, and held so nothing writes meanwhile
+    let ready = unsafe { Port::<u8>::new(LINE_STATUS).read() } & DATA_READY != 0;
+    let byte = if ready {Some(unsafe { Port::<u8>::new(COM1).read() })} else {None};
+    drop(port);
+    byte
+}
@@ -0,0 +1,103 @@
+// Sound effects on the platform's one-voice speaker. Each effect is a short
+// sequence of tones played out one timer tick at a time so nothing ever
+// blocks.
+
+use spin::Mutex;
+
+use crate::MainGame;
+use crate::game_core::Sound;
+use crate::platform::Platform;
+
+// A pitch held for some timer ticks; a pitch of 0 is a rest
+#[derive(Copy,Clone,Eq,PartialEq,Debug)]
+struct Tone {
+    hz: u32,
+    ticks: u8
+}
+
+const fn tone(hz: u32, ticks: u8) -> Tone {
+    Tone {hz, ticks}
+}
+
+const MARCH: [[Tone; 1]; 4] = [[tone(98, 2)], [tone(87, 2)], [tone(78, 2)], [tone(73, 2)]];
+const SHOT: [Tone; 3] = [tone(1400, 1), tone(1000, 1), tone(700, 1)];
+const ALIEN_EXPLOSION: [Tone; 4] = [tone(220, 1), tone(150, 1), tone(110, 1), tone(80, 1)];
+const UFO: [Tone; 2] = [tone(740, 1), tone(990, 1)];
+const PLAYER_DEATH: [Tone; 6] = [tone(440, 2), tone(330, 2), tone(0, 1), tone(247, 2), tone(165, 3), tone(110, 4)];
+
+fn tones(sound: Sound) -> &'static [Tone] {
+    match sound {
+        Sound::March(note) => &MARCH[note as usize % MARCH.len()],
+        Sound::Shot => &SHOT,
+        Sound::AlienExplosion => &ALIEN_EXPLOSION,
+        Sound::Ufo => &UFO,
+        Sound::PlayerDeath => &PLAYER_DEATH
+    }
+}
+
+// There is one speaker, so a sound only interrupts one that matters as much
+fn priority(sound: Sound) -> u8 {
+    match sound {
+        Sound::March(_) => 1,
+        Sound::Ufo => 2,
+        Sound::Shot => 3,
+        Sound::AlienExplosion => 4,
+        Sound::PlayerDeath => 5
+    }
+}
+
+struct Speaker {
+    tones: &'static [Tone],
+    next: usize,
+    ticks_left: u8,
+    priority: u8 // of the sound playing, 0 when silent
+}
+
+static SPEAKER: Mutex<Speaker> = Mutex::new(Speaker {tones: &[], next: 0, ticks_left: 0, priority: 0});
+
+// Called every timer tick: picks up the game's new sounds and moves the
+// current one on by a tick
+pub fn update<P: Platform>(platform: &mut P, game: &mut MainGame) {
+    let mut speaker = SPEAKER.lock();
+    if game.muted() {
+        speaker.stop(platform);
+        return
+    }
+    for sound in game.take_sounds().iter() {
+        speaker.start(*sound);
+    }
+    speaker.advance(platform);
+}
+
+impl Speaker {
+    fn start(&mut self, sound: Sound) {
+        if priority(sound) >= self.priority {
+            self.tones = tones(sound);
+            self.next = 0;
+            self.ticks_left = 0;
+            self.priority = priority(sound);
+        }
+    }
+
+    fn advance<P: Platform>(&mut self, platform: &mut P) {
+        if self.ticks_left > 0 {
+            self.ticks_left -= 1;
+        } else if let Some(tone) = self.tones.get(self.next) {
+            self.next += 1;
+            self.ticks_left = tone.ticks.saturating_sub(1);
+            platform.play_tone(tone.hz);
+        } else {
+            self.stop(platform);
+        }
+    }
+
+    fn stop<P: Platform>(&mut self, platform: &mut P) {
+        if self.priority > 0 {
+            self.tones = &[];
+            self.next = 0;
+            self.ticks_left = 0;
+            self.priority = 0;
+            platform.play_tone(0);
+        }
+    }
+}
@@ -0,0 +1,81 @@
+// The kernel's platform: pluggable_interrupt_os's VGA text buffer, the time
+// stamp counter and the PC speaker, which PIT channel 2 drives with a square
+// wave at the tone's pitch.
+
+use core::arch::x86_64::_rdtsc;
+use x86_64::instructions::port::Port;
+use pluggable_interrupt_os::vga_buffer::{self, ColorCode};
+
+use crate::platform::{Platform, Color};
+
+const PIT_FREQUENCY: u32 = 1_193_182;
+const PIT_CHANNEL_2: u16 = 0x42;
+const PIT_COMMAND: u16 = 0x43;
+const SPEAKER_CONTROL: u16 = 0x61;
+// Channel 2, low byte then high byte, mode 3 (square wave), binary
+const SQUARE_WAVE: u8 = 0b1011_0110;
+// Bit 0 gates PIT channel 2, bit 1 connects it to the speaker
+const SPEAKER_ON: u8 = 0b11;
+
+pub struct VgaPlatform;
+
+fn vga_color(color: Color) -> vga_buffer::Color {
+    match color {
+        Color::Black => vga_buffer::Color::Black,
+        Color::Blue => vga_buffer::Color::Blue,
+        Color::Green => vga_buffer::Color::Green,
+        Color::Cyan => vga_buffer::Color::Cyan,
+        Color::Red => vga_buffer::Color::Red,
+        Color::Magenta => vga_buffer::Color::Magenta,
+        Color::Brown => vga_buffer::Color::Brown,
+        Color::LightGray => vga_buffer::Color::LightGray,
+        Color::DarkGray => vga_buffer::Color::DarkGray,
+        Color::LightBlue => vga_buffer::Color::LightBlue,
+        Color::LightGreen => vga_buffer::Color::LightGreen,
+        Color::LightCyan => vga_buffer::Color::LightCyan,
+        Color::LightRed => vga_buffer::Color::LightRed,
+        Color::Pink => vga_buffer::Color::Pink,
+        Color::Yellow => vga_buffer::Color::Yellow,
+        Color::White => vga_buffer::Color::White
+    }
+}
+
+impl Platform for VgaPlatform {
+    fn plot(&mut self, c: char, col: usize, row: usize, color: Color) {
+        vga_buffer::plot(c, col, row, ColorCode::new(vga_color(color), vga_buffer::Color::Black));
+    }
+
+    fn clear_row(&mut self, row: usize) {
+        vga_buffer::clear_row(row, vga_buffer::Color::Black);
+    }
+
+    fn now_ticks(&self) -> u64 {
+        unsafe { _rdtsc() }
+    }
+
+    fn play_tone(&mut self, hz: u32) {
+        if hz == 0 {
+            speaker_off();
+            return
+        }
+        let divisor = (PIT_FREQUENCY / hz).min(u16::MAX as u32) as u16;
+        let mut command: Port<u8> = Port::new(PIT_COMMAND);
+        let mut channel: Port<u8> = Port::new(PIT_CHANNEL_2);
+        let mut control: Port<u8> = Port::new(SPEAKER_CONTROL);
+        unsafe {
+            command.write(SQUARE_WAVE);
+            channel.write(divisor as u8);
+            channel.write((divisor >> 8) as u8);
+            let bits = control.read();
+            control.write(bits | SPEAKER_ON);
+        }
+    }
+}
+
+fn speaker_off() {
+    let mut control: Port<u8> = Port::new(SPEAKER_CONTROL);
+    unsafe {
+        let bits = control.read();
+        control.write(bits & !SPEAKER_ON);
+    }
+}
@@ -0,0 +1,508 @@
+// Lays out the 80x25 text screen. Each frame is composed off screen, then
+// only the cells that changed since the last one are written to the
+// platform's screen.
+
+use core::fmt::{self, Write};
+use pc_keyboard::DecodedKey;
+
+use crate::platform::{Platform, Color, SCREEN_WIDTH, SCREEN_HEIGHT};
+
+#[cfg(test)]
+mod snapshots;
+
+use crate::MainGame;
+use crate::game_core::high_scores::{MAX_HIGH_SCORES, INITIALS_LEN};
+use crate::game_core::input::{Action, KeyLabel, ACTIONS, PRESETS};
+use crate::game_core::{Status, Cell, Position, Player, Alien, AlienKind, EntityRef, BOARD_HEIGHT, MAX_LIVES};
+
+const HEADER_SPACE: usize = SCREEN_HEIGHT - BOARD_HEIGHT;
+const HIGH_SCORE_WIDTH: usize = 20;
+// Code page 437 shade blocks, from one hit left up to an undamaged bunker
+const BARRIER_GLYPHS: [u8; 4] = [0xB0, 0xB1, 0xB2, 0xDB];
+const OPTIONS_LEFT: usize = 20;
+// The newest frame counts for 1/FRAME_AVERAGE_WEIGHT of the running average
+const FRAME_AVERAGE_WEIGHT: u64 = 16;
+
+#[derive(Copy,Clone,Eq,PartialEq)]
+struct ScreenCell {
+    c: char,
+    foreground: Color
+}
+
+const BLANK: ScreenCell = ScreenCell {c: ' ', foreground: Color::Black};
+
+#[derive(Copy,Clone,Eq,PartialEq)]
+struct Frame {
+    cells: [[ScreenCell; SCREEN_WIDTH]; SCREEN_HEIGHT]
+}
+
+impl Frame {
+    const fn new() -> Self {
+        Frame {cells: [[BLANK; SCREEN_WIDTH]; SCREEN_HEIGHT]}
+    }
+
+    fn clear(&mut self) {
+        self.cells = [[BLANK; SCREEN_WIDTH]; SCREEN_HEIGHT];
+    }
+
+    fn plot(&mut self, c: char, col: usize, row: usize, foreground: Color) {
+        if row < SCREEN_HEIGHT && col < SCREEN_WIDTH {
+            self.cells[row][col] = ScreenCell {c, foreground};
+        }
+    }
+
+    // Returns the column after the text
+    fn plot_str(&mut self, text: &str, col: usize, row: usize, foreground: Color) -> usize {
+        for (i, c) in text.chars().enumerate() {
+            self.plot(c, col + i, row, foreground);
+        }
+        col + text.chars().count()
+    }
+
+    fn plot_num(&mut self, num: u64, col: usize, row: usize, foreground: Color) -> usize {
+        let mut digits = [0u8; 20];
+        let mut len = 0;
+        let mut rest = num;
+        loop {
+            digits[len] = b'0' + (rest % 10) as u8;
+            len += 1;
+            rest /= 10;
+            if rest == 0 {
+                break
+            }
+        }
+        for i in 0..len {
+            self.plot(digits[len - 1 - i] as char, col + i, row, foreground);
+        }
+        col + len
+    }
+
+    fn plot_centered(&mut self, text: &str, row: usize, foreground: Color) {
+        self.plot_str(text, (SCREEN_WIDTH - text.len()) / 2, row, foreground);
+    }
+}
+
+// Formatted text of up to a row, built before it is plotted so it can be
+// centered; anything longer is cut off
+struct Line {
+    text: [u8; SCREEN_WIDTH],
+    len: usize
+}
+
+impl Line {
+    fn new(args: fmt::Arguments) -> Self {
+        let mut line = Line {text: [b' '; SCREEN_WIDTH], len: 0};
+        let _ = line.write_fmt(args);
+        line
+    }
+
+    fn as_str(&self) -> &str {
+        core::str::from_utf8(&self.text[..self.len]).unwrap_or("")
+    }
+}
+
+impl Write for Line {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for c in s.chars() {
+            if self.len == SCREEN_WIDTH {
+                break
+            }
+            self.text[self.len] = if c.is_ascii() {c as u8} else {b'?'};
+            self.len += 1;
+        }
+        Ok(())
+    }
+}
+
+// The first key bound to the action, for showing in instructions
+fn key_label(game: &MainGame, action: Action) -> KeyLabel {
+    KeyLabel(game.bindings().keys(action).next().unwrap_or(DecodedKey::Unicode('?')))
+}
+
+pub struct Renderer {
+    shown: Frame, // what the platform's screen holds
+    next: Frame,
+    synced: bool, // false until the whole screen has been written once
+    average_cycles: u64
+}
+
+impl Default for Renderer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Renderer {
+    pub const fn new() -> Self {
+        Renderer {shown: Frame::new(), next: Frame::new(), synced: false, average_cycles: 0}
+    }
+
+    pub fn draw<P: Platform>(&mut self, platform: &mut P, game: &MainGame) {
+        let start = platform.now_ticks();
+        self.compose(game);
+        self.flush(platform);
+        platform.present();
+        self.record_frame_time(platform.now_ticks().wrapping_sub(start));
+    }
+
+    fn compose(&mut self, game: &MainGame) {
+        self.next.clear();
+        let frame = &mut self.next;
+        match game.status() {
+            Status::Title => draw_title_screen(frame, game),
+            Status::Options => draw_options_screen(frame, game),
+            status => {
+                if game.frame_time() {
+                    draw_frame_time(frame, self.average_cycles);
+                }
+                draw_header(frame, game);
+                if game.demo() {
+                    frame.plot_centered("DEMO - press any key", 0, Color::LightRed);
+                }
+                draw_board(frame, game);
+                if let Status::EnteringInitials | Status::Over = status {
+                    draw_high_score_box(frame, game);
+                }
+            }
+        }
+    }
+
+    // Rows that have gone blank are cleared in one go
+    fn flush<P: Platform>(&mut self, platform: &mut P) {
+        for row in 0..SCREEN_HEIGHT {
+            let next = &self.next.cells[row];
+            if self.synced && *next == self.shown.cells[row] {
+                continue
+            }
+            if next.iter().all(|cell| *cell == BLANK) {
+                platform.clear_row(row);
+                continue
+            }
+            for (col, cell) in next.iter().enumerate() {
+                if !self.synced || *cell != self.shown.cells[row][col] {
+                    platform.plot(cell.c, col, row, cell.foreground);
+                }
+            }
+        }
+        self.shown = self.next;
+        self.synced = true;
+    }
+
+    fn record_frame_time(&mut self, cycles: u64) {
+        self.average_cycles = if self.average_cycles == 0 {
+            cycles
+        } else {
+            self.average_cycles - self.average_cycles / FRAME_AVERAGE_WEIGHT + cycles / FRAME_AVERAGE_WEIGHT
+        };
+    }
+}
+
+fn draw_frame_time(frame: &mut Frame, average_cycles: u64) {
+    let label = "cyc/frame";
+    let mut digits = 1;
+    let mut rest = average_cycles / 10;
+    while rest > 0 {
+        digits += 1;
+        rest /= 10;
+    }
+    let col = SCREEN_WIDTH - label.len() - 1 - digits;
+    frame.plot_num(average_cycles, col, 1, Color::DarkGray);
+    frame.plot_str(label, col + digits + 1, 1, Color::DarkGray);
+}
+
+fn draw_header(frame: &mut Frame, game: &MainGame) {
+    match game.status() {
+        Status::Normal => {
+            draw_normal_header(frame, game);
+            draw_ufo_popup(frame, game);
+        }
+        Status::Paused => draw_paused_header(frame, game),
+        Status::NextWave => draw_next_wave_header(frame, game),
+        Status::Dying => draw_normal_header(frame, game),
+        Status::NextPlayer => draw_next_player_header(frame, game),
+        Status::EnteringInitials => {
+            draw_normal_header(frame, game);
+            draw_subheader(frame, "New high score! Type your initials, then press Enter.");
+        }
+        Status::Over => draw_game_over_header(frame, game),
+        Status::Title | Status::Options => {}
+    }
+}
+
+fn draw_normal_header(frame: &mut Frame, game: &MainGame) {
+    if game.players() == 2 {
+        draw_player_scores(frame, game);
+    } else {
+        let score_text = "Score:";
+        frame.plot_str(score_text, 0, 0, Color::White);
+        frame.plot_num(game.score(), score_text.len() + 1, 0, Color::White);
+    }
+    draw_lives(frame, game);
+}
+
+// Both scores side by side, with the player whose turn it is in yellow
+fn draw_player_scores(frame: &mut Frame, game: &MainGame) {
+    let mut col = 0;
+    for player in 0..game.players() as usize {
+        let color = if player == game.current_player() {Color::Yellow} else {Color::White};
+        let text = Line::new(format_args!("Player {}: {}", player + 1, game.player_score(player)));
+        frame.plot_str(text.as_str(), col, 0, color);
+        col += text.len + 3;
+    }
+}
+
+fn draw_lives(frame: &mut Frame, game: &MainGame) {
+    let lives_text = "Lives:";
+    let lives_col = SCREEN_WIDTH - lives_text.len() - 1 - MAX_LIVES as usize;
+    frame.plot_str(lives_text, lives_col, 0, Color::White);
+    for life in 0..game.lives() as usize {
+        frame.plot(Player::icon(), lives_col + lives_text.len() + 1 + life, 0, Color::Yellow);
+    }
+}
+
+fn draw_ufo_popup(frame: &mut Frame, game: &MainGame) {
+    if let Some(popup) = game.ufo_popup() {
+        frame.plot_num(popup.points(), popup.col(), 1, Color::Magenta);
+    }
+}
+
+fn draw_subheader(frame: &mut Frame, subheader: &str) {
+    frame.plot_str(subheader, 0, 1, Color::LightRed);
+}
+
+fn draw_next_wave_header(frame: &mut Frame, game: &MainGame) {
+    draw_normal_header(frame, game);
+    let wave_text = "Wave";
+    frame.plot_str(wave_text, 0, 1, Color::LightGreen);
+    frame.plot_num(game.wave() as u64, wave_text.len() + 1, 1, Color::LightGreen);
+}
+
+fn draw_next_player_header(frame: &mut Frame, game: &MainGame) {
+    draw_normal_header(frame, game);
+    let text = Line::new(format_args!("Player {}, get ready", game.current_player() + 1));
+    frame.plot_str(text.as_str(), 0, 1, Color::LightGreen);
+}
+
+fn draw_paused_header(frame: &mut Frame, game: &MainGame) {
+    draw_normal_header(frame, game);
+    let text = Line::new(format_args!("Paused. Press {} to resume.", key_label(game, Action::Pause)));
+    draw_subheader(frame, text.as_str());
+}
+
+fn draw_game_over_header(frame: &mut Frame, game: &MainGame) {
+    draw_normal_header(frame, game);
+    let text = Line::new(format_args!("Game over. Press {} to restart.", key_label(game, Action::Restart)));
+    draw_subheader(frame, text.as_str());
+}
+
+fn draw_board(frame: &mut Frame, game: &MainGame) {
+    for p in game.cell_pos_iter() {
+        let (row, col) = p.row_col();
+        let (c, color) = get_icon_color(game, p, &game.cell(p));
+        frame.plot(c, col, row + HEADER_SPACE, color);
+    }
+}
+
+fn get_icon_color(game: &MainGame, p: Position, cell: &Cell) -> (char, Color) {
+    match game.entity_at(p) {
+        Some(EntityRef::Player(_)) => (match game.status() {
+            Status::Over => '*',
+            Status::Dying if game.player_exploding() => ['*', '+'][game.respawn_countdown() % 2],
+            _ => Player::icon()
+        }, Color::Yellow),
+        Some(EntityRef::Ufo(ufo)) => (ufo.icon_at(p).unwrap_or(' '), Color::Magenta),
+        Some(EntityRef::Alien(_, alien)) => (alien.icon(), alien_color(alien)),
+        Some(EntityRef::Shot(shot)) => (shot.icon(), Color::Red),
+        None => match cell {
+            Cell::Empty => ('.', Color::White),
+            Cell::Barrier(health) => (barrier_glyph(*health), Color::Blue),
+            Cell::Wall => ('#', Color::Blue)
+        }
+    }
+}
+
+fn barrier_glyph(health: u8) -> char {
+    let stage = (health as usize).clamp(1, BARRIER_GLYPHS.len()) - 1;
+    BARRIER_GLYPHS[stage] as char
+}
+
+fn alien_color(alien: &Alien) -> Color {
+    if alien.kind() == AlienKind::Crab && alien.damaged() {
+        Color::Cyan
+    } else {
+        alien_kind_color(alien.kind())
+    }
+}
+
+fn alien_kind_color(kind: AlienKind) -> Color {
+    match kind {
+        AlienKind::Squid => Color::Pink,
+        AlienKind::Crab => Color::LightCyan,
+        AlienKind::Octopus => Color::Green
+    }
+}
+
+fn draw_title_screen(frame: &mut Frame, game: &MainGame) {
+    frame.plot_centered("S P A C E   I N V A D E R S", 1, Color::LightGreen);
+    frame.plot_centered("*SCORE ADVANCE TABLE*", 3, Color::White);
+    let kinds = [AlienKind::Squid, AlienKind::Crab, AlienKind::Octopus];
+    for (i, kind) in kinds.iter().enumerate() {
+        let row = 4 + i;
+        let col = SCREEN_WIDTH / 2 - 6;
+        frame.plot(kind.icon(), col, row, alien_kind_color(*kind));
+        frame.plot_str("=", col + 2, row, Color::White);
+        frame.plot_num(kind.points(), col + 4, row, Color::White);
+        frame.plot_str("POINTS", col + 7, row, Color::White);
+    }
+    let ufo_row = 4 + kinds.len();
+    frame.plot_str("<O> = ? MYSTERY", SCREEN_WIDTH / 2 - 7, ufo_row, Color::Magenta);
+    draw_high_scores(frame, game, 9, None);
+    let controls = Line::new(format_args!("{}/{} move   {} fires   {} pauses   {} mutes",
+        key_label(game, Action::MoveLeft), key_label(game, Action::MoveRight), key_label(game, Action::Fire),
+        key_label(game, Action::Pause), key_label(game, Action::Mute)));
+    frame.plot_centered(controls.as_str(), 21, Color::LightGray);
+    let options = Line::new(format_args!("Press {} for options", key_label(game, Action::Options)));
+    frame.plot_centered(options.as_str(), 22, Color::DarkGray);
+    let start = Line::new(format_args!("Press {} to start, {} for two players",
+        key_label(game, Action::Start), key_label(game, Action::TwoPlayers)));
+    frame.plot_centered(start.as_str(), 24, Color::Yellow);
+}
+
+// Every action with its keys, numbered for rebinding, and the presets
+fn draw_options_screen(frame: &mut Frame, game: &MainGame) {
+    frame.plot_centered("O P T I O N S", 1, Color::LightGreen);
+    frame.plot_centered("KEY BINDINGS", 3, Color::White);
+    for (i, action) in ACTIONS.iter().enumerate() {
+        let row = 5 + i;
+        let waiting = game.rebinding() == Some(*action);
+        let color = if waiting {Color::Yellow} else {Color::LightGray};
+        let name = Line::new(format_args!("{}. {}", action.menu_key(), action.name()));
+        frame.plot_str(name.as_str(), OPTIONS_LEFT, row, color);
+        let mut keys = Line::new(format_args!(""));
+        if waiting {
+            let _ = write!(keys, "press a key, Esc cancels");
+        } else {
+            for (n, key) in game.bindings().keys(*action).enumerate() {
+                let _ = write!(keys, "{}{}", if n == 0 {""} else {" / "}, KeyLabel(key));
+            }
+        }
+        frame.plot_str(keys.as_str(), OPTIONS_LEFT + 16, row, color);
+    }
+    let presets_row = 6 + ACTIONS.len();
+    frame.plot_str("PRESETS", OPTIONS_LEFT, presets_row, Color::White);
+    for (i, preset) in PRESETS.iter().enumerate() {
+        let text = Line::new(format_args!("{}  {}", KeyLabel(DecodedKey::Unicode(preset.menu_key())), preset.name()));
+        frame.plot_str(text.as_str(), OPTIONS_LEFT, presets_row + 1 + i, Color::LightGray);
+    }
+    frame.plot_centered("1-9, 0 rebind an action   Enter returns to the title", 22, Color::LightGray);
+}
+
+// The table over the middle of the board, with the score being entered in
+// its place while the player types their initials
+fn draw_high_score_box(frame: &mut Frame, game: &MainGame) {
+    let top = HEADER_SPACE + 4;
+    let left = (SCREEN_WIDTH - HIGH_SCORE_WIDTH - 2) / 2;
+    for row in top..top + MAX_HIGH_SCORES + 3 {
+        for col in left..left + HIGH_SCORE_WIDTH + 2 {
+            frame.plot(' ', col, row, Color::White);
+        }
+    }
+    let pending = match game.status() {
+        Status::EnteringInitials => game.high_scores().rank(game.score()),
+        _ => None
+    };
+    draw_high_scores(frame, game, top + 1, pending);
+}
+
+fn draw_high_scores(frame: &mut Frame, game: &MainGame, top: usize, pending: Option<usize>) {
+    let left = (SCREEN_WIDTH - HIGH_SCORE_WIDTH) / 2;
+    frame.plot_centered("HIGH SCORES", top, Color::White);
+    let table = game.high_scores();
+    if table.is_empty() && pending.is_none() {
+        frame.plot_centered("No scores yet", top + 2, Color::LightGray);
+        return
+    }
+    let mut entries = table.iter();
+    let shown = (table.len() + pending.map_or(0, |_| 1)).min(MAX_HIGH_SCORES);
+    for rank in 0..shown {
+        let row = top + 1 + rank;
+        let (initials, score, color) = if pending == Some(rank) {
+            (game.initials(), game.score(), Color::Yellow)
+        } else {
+            let entry = entries.next().unwrap();
+            (entry.initials(), entry.score(), Color::LightGray)
+        };
+        let place = rank + 1;
+        frame.plot_num(place as u64, if place < 10 {left + 1} else {left}, row, color);
+        frame.plot_str(".", left + 2, row, color);
+        frame.plot_str(initials, left + 4, row, color);
+        for col in initials.len()..INITIALS_LEN {
+            frame.plot('_', left + 4 + col, row, color);
+        }
+        frame.plot_num(score, left + 4 + INITIALS_LEN + 2, row, color);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_core::sim::*;
+    use crate::platform::MemoryPlatform;
+
+    fn row_text(platform: &MemoryPlatform, row: usize) -> String {
+        platform.row_text(row).collect()
+    }
+
+    #[test]
+    fn board_cells_show_what_stands_on_them() {
+        let mut sim = Sim::blank();
+        let (row, col) = sim.player_pos();
+        sim.place_alien(3, 10).place_barrier(row - 4, col + 1).hold_formation().hold_alien_fire();
+        sim.play(&[FIRE, Input::Updates(1)]);
+        let mut platform = MemoryPlatform::new();
+        Renderer::new().draw(&mut platform, &sim.game);
+        let at = |row: usize, col: usize| (platform.char_at(col, row + HEADER_SPACE), platform.color_at(col, row + HEADER_SPACE));
+        assert_eq!(at(row, col), ('^', Color::Yellow));
+        assert_eq!(at(3, 10), ('@', Color::Green));
+        assert_eq!(at(row - 4, col + 1), (BARRIER_GLYPHS[3] as char, Color::Blue));
+        assert_eq!(at(row - 2, col), ('|', Color::Red));
+        assert_eq!(at(0, 0), ('.', Color::White));
+        assert!(row_text(&platform, 0).starts_with("Score: 0"));
+    }
+
+    #[test]
+    fn frame_time_shows_only_when_switched_on() {
+        let mut sim = Sim::new();
+        let mut platform = MemoryPlatform::new();
+        let mut renderer = Renderer::new();
+        renderer.draw(&mut platform, &sim.game);
+        assert!(!row_text(&platform, 1).contains("cyc/frame"));
+        sim.game.set_frame_time(true);
+        renderer.draw(&mut platform, &sim.game);
+        assert!(row_text(&platform, 1).ends_with("cyc/frame"));
+    }
+
+    #[test]
+    fn only_changed_cells_are_written() {
+        let mut sim = Sim::blank();
+        sim.place_alien(3, 10).hold_formation().hold_alien_fire();
+        let mut platform = MemoryPlatform::new();
+        let mut renderer = Renderer::new();
+        renderer.draw(&mut platform, &sim.game);
+        assert!(platform.plotted() > BOARD_HEIGHT * 80);
+        renderer.draw(&mut platform, &sim.game);
+        assert_eq!(platform.plotted(), 0);
+        sim.play(&[LEFT, Input::Updates(1)]);
+        renderer.draw(&mut platform, &sim.game);
+        assert_eq!((platform.plotted(), platform.frames()), (2, 3));
+    }
+
+    #[test]
+    fn title_screen_shows_the_bound_keys() {
+        let mut platform = MemoryPlatform::new();
+        Renderer::new().draw(&mut platform, &MainGame::new());
+        assert_eq!(row_text(&platform, 1).trim(), "S P A C E   I N V A D E R S");
+        assert_eq!(row_text(&platform, 21).trim(), "Left/Right move   Space fires   P pauses   M mutes");
+        assert_eq!(row_text(&platform, 24).trim(), "Press Enter to start, 2 for two players");
+    }
+}
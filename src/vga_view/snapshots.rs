@@ -0,0 +1,66 @@
+// Golden-frame tests: games in known states are rendered to a
+// MemoryPlatform and the screen compared with the snapshot checked in under
+// fixtures/frames. After a deliberate change to the screen, run the tests
+// with UPDATE_SNAPSHOTS=1 to rewrite the fixtures, then review the diff.
+
+use std::fs;
+use std::path::PathBuf;
+use super::Renderer;
+use crate::MainGame;
+use crate::game_core::sim::*;
+use crate::platform::MemoryPlatform;
+
+fn fixture_path(name: &str) -> PathBuf {
+    [env!("CARGO_MANIFEST_DIR"), "fixtures", "frames", &format!("{}.txt", name)].iter().collect()
+}
+
+fn assert_snapshot(name: &str, game: &MainGame) {
+    let mut platform = MemoryPlatform::new();
+    Renderer::new().draw(&mut platform, game);
+    let mut screen = String::new();
+    platform.write_snapshot(&mut screen).unwrap();
+    let path = fixture_path(name);
+    if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, &screen).unwrap();
+        return
+    }
+    let expected = fs::read_to_string(&path)
+        .unwrap_or_else(|e| panic!("{}: {}; run with UPDATE_SNAPSHOTS=1 to create it", path.display(), e));
+    if let Some((line, (want, got))) = expected.lines().zip(screen.lines()).enumerate().find(|(_, (want, got))| want != got) {
+        panic!("{} differs at line {}:\nexpected: {}\n     got: {}\nrun with UPDATE_SNAPSHOTS=1 if the change is intended\n\n{}",
+               name, line + 1, want, got, screen);
+    }
+    assert_eq!(expected.lines().count(), screen.lines().count(), "{} has a different number of lines", name);
+}
+
+#[test]
+fn initial_board() {
+    assert_snapshot("initial_board", &Sim::new().game);
+}
+
+#[test]
+fn game_over_header() {
+    let mut sim = Sim::new();
+    sim.lose_last_life();
+    assert_snapshot("game_over", &sim.game);
+}
+
+// The bunker over the player's start takes three lasers, two of them
+// into the same cell
+#[test]
+fn partially_destroyed_bunker() {
+    let mut sim = Sim::new();
+    sim.hold_formation().hold_alien_fire();
+    sim.play(&[FIRE, Input::Updates(6), FIRE, Input::Updates(6), RIGHT, Input::Updates(1), FIRE, Input::Updates(6)]);
+    assert_snapshot("damaged_bunker", &sim.game);
+}
+
+// A laser fired through the gap between two bunkers, halfway to the formation
+#[test]
+fn shot_in_flight() {
+    let mut sim = Sim::new();
+    sim.hold_formation().hold_alien_fire();
+    sim.play(&[LEFT, Input::Updates(1), LEFT, Input::Updates(1), FIRE, Input::Updates(8)]);
+    assert_snapshot("shot_in_flight", &sim.game);
+}